@@ -6,7 +6,11 @@
   craneLib = import crane {inherit pkgs;};
 
   commonArgs = {
-    src = craneLib.cleanCargoSource ./.;
+    # Keep the embedded translations alongside the Rust sources
+    src = pkgs.lib.cleanSourceWith {
+      src = ./.;
+      filter = path: type: (pkgs.lib.hasSuffix ".ftl" path) || (craneLib.filterCargoSources path type);
+    };
     strictDeps = true;
   };
 in
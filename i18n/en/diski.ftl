@@ -0,0 +1,151 @@
+# Tray
+
+tray-title = { $name } Status
+tray-title-path = { $name } Status ({ $path })
+tooltip-polkit-missing = polkit is not running, so diski can't be authorized to mount or unmount drives
+
+menu-bus-disconnected = Disconnected from bus, reconnecting…
+menu-polkit-missing = Actions are unavailable without polkit
+menu-mount-state = Mount: { $state }
+menu-swap-state = Swap: { $state }
+menu-automount-state = Automount: { $state }
+menu-last-mounted = Last mounted: { $ago }
+menu-last-unmounted = Last unmounted: { $ago }
+menu-path = Path: { $path }
+menu-fs = Fs: { $fs }
+menu-options = Options: { $options }
+menu-label = Label: { $label }
+menu-uuid = UUID: { $uuid }
+menu-device = Device: { $device }
+menu-free = Free: { $space }
+menu-health-ok = Health: OK
+menu-health-ok-temperature = Health: OK ({ $celsius }°C)
+menu-health-failing = Health: FAILING
+
+# Actions mark their access key with _, only kept with menu_mnemonics
+menu-unlock = _Unlock
+menu-open-folder = _Open folder
+menu-copy-path = _Copy path
+menu-disconnect-drive = Disconnect _whole drive
+menu-disconnect = _Disconnect
+menu-mount = _Mount now
+menu-unmount = U_nmount (keep automount)
+menu-swap-on = _Activate swap
+menu-swap-off = _Deactivate swap
+menu-remount-read-only = Remount _read-only
+menu-remount-read-write = Remount _read-write
+menu-remount = Remoun_t
+menu-automount = _Automount
+menu-automount-cooldown = _Automount (retry in { $seconds }s)
+menu-automount-at-boot = Automount at _boot
+menu-quiet = Quiet noti_fications
+menu-emergency-disconnect = _Emergency disconnect
+menu-reset-failed = C_lear error
+menu-advanced = Ad_vanced
+menu-refresh = _Check now
+menu-reload-daemon = Re_load systemd
+menu-about = About d_iski v{ $version }
+menu-quit = _Quit
+
+# Unit states
+
+mount-mounted = Mounted
+mount-mounting = Mounting…
+mount-finalizing = Finalizing…
+mount-unmounting = Unmounting…
+mount-dead = Not mounted
+mount-failed = Error
+
+swap-active = Active
+swap-activating = Activating…
+swap-finalizing = Finalizing…
+swap-deactivating = Deactivating…
+swap-dead = Off
+
+automount-dead = Off
+automount-waiting = Ready
+automount-running = Active
+automount-failed = Error
+
+state-unknown = Unknown ({ $state })
+
+# Notifications
+
+notify-summary = { $drive }
+notify-disconnected = Drive has been fully unmounted
+notify-safe-to-remove = Safe to remove drive
+notify-power-off-failed = Drive has been fully unmounted but could not be powered off: { $error }
+notify-automount-enabled = Automounting has been enabled
+notify-automount-verified = Automount enabled, the drive mounted when opened
+notify-automount-disabled = Automounting has been disabled
+notify-automount-at-boot = Automount will be enabled at boot
+notify-no-automount-at-boot = Automount will no longer be enabled at boot
+notify-mounted = Drive has been mounted
+notify-mounted-after-retries = Drive has been mounted after { $attempts } attempts
+notify-unmounted = Drive has been unmounted, automount is still active
+notify-swap-on = Swap has been activated
+notify-swap-off = Swap has been deactivated
+notify-remounted = Drive has been remounted
+notify-read-only = Drive has been remounted read-only
+notify-read-write = Drive has been remounted read-write
+notify-daemon-reloaded = systemd has been reloaded
+notify-all-drives = All drives
+notify-mounted-all = { $mounted } mounted, { $failed } failed
+notify-disconnected-all = { $disconnected } disconnected, { $failed } failed
+notify-reset-failed = The error was cleared, the drive can be mounted again
+notify-remount-failed = Drive was unmounted but could not be mounted again: { $error }
+notify-enable-automount = Re-enable automount
+notify-in-use = Files on the drive are still open in { $processes }
+notify-unverified-idle = Could not verify the drive is idle, files on it may still be open
+notify-disconnect-anyway = Disconnect anyway
+notify-cancel = Cancel
+notify-confirm-disconnect = Unmount the drive and stop automounting it?
+notify-confirm = Confirm
+notify-lazy-unmount = Force (lazy) unmount
+notify-copied = Copied { $path }
+notify-copy-failed = Could not copy the path: { $error }
+notify-low-space = Only { $free } left on { $path }
+notify-health-failing = SMART reports the drive is failing, back up its data and replace it
+notify-device-vanished = The drive was removed while it was still mounted, unmount before unplugging it
+notify-lazily-unmounted = Drive has been detached, it is released once its open files are closed
+notify-dry-run = (dry-run) { $body }
+notify-request-failed = { $request } failed: { $error }
+notify-not-authorized = { $request } was not authorized
+notify-polkit-missing = { $request } needs polkit, which is not available
+
+# Requests, as the subject of a failure notification
+
+request-disconnect = Disconnecting
+request-enable-automount = Enabling automount
+request-disable-automount = Disabling automount
+request-automount-at-boot = Changing automount at boot
+request-mount = Mounting
+request-unmount = Unmounting
+request-remount = Remounting
+request-force-unmount = Force unmounting
+request-emergency-disconnect = Emergency disconnect
+request-disconnect-drive = Disconnecting the whole drive
+request-unlock = Unlocking
+request-read-only = Remounting read-only
+request-read-write = Remounting read-write
+request-reload-daemon = Reloading systemd
+request-reset-failed = Clearing the error
+request-mount-all = Mounting every drive
+request-disconnect-all = Disconnecting every drive
+
+job-mounting = Mounting…
+job-unmounting = Unmounting…
+job-enabling-automount = Enabling automount…
+job-disabling-automount = Disabling automount…
+job-failed = { $unit } job finished with result '{ $result }'
+job-unsettled = { $unit } ended up { $state } without systemd reporting how its job went
+job-timed-out = systemd did not finish the job within { $seconds }s, the operation may still be in progress
+job-connection-lost = lost connection to systemd while waiting for the job
+
+ago-now = just now
+ago-minutes = { $n } min ago
+ago-hours = { $n } h ago
+ago-days = { $n } d ago
+
+# Shown by tools like `systemd-inhibit --list`
+inhibit-sync = Syncing mounted drives before sleeping
@@ -0,0 +1,198 @@
+use std::{
+    collections::HashMap,
+    env, os,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use zbus::{
+    proxy,
+    zvariant::{OwnedObjectPath, OwnedValue, Str},
+};
+use zbus_polkit::policykit1::{AuthorityProxy, CheckAuthorizationFlags, Subject};
+
+use crate::{config::PolkitSubject, ExResult};
+
+const POLKIT: &str = "org.freedesktop.PolicyKit1";
+
+pub const MANAGE_UNITS: &str = "org.freedesktop.systemd1.manage-units";
+pub const MANAGE_UNIT_FILES: &str = "org.freedesktop.systemd1.manage-unit-files";
+pub const RELOAD_DAEMON: &str = "org.freedesktop.systemd1.reload-daemon";
+pub const UNMOUNT_OTHERS: &str = "org.freedesktop.udisks2.filesystem-unmount-others";
+pub const POWER_OFF_DRIVE: &str = "org.freedesktop.udisks2.power-off-drive";
+pub const UNLOCK_ENCRYPTED: &str = "org.freedesktop.udisks2.encrypted-unlock";
+
+/// Whether polkit is running or can be started on demand, minimal systems
+/// sometimes ship without it.
+pub async fn polkit_available(conn: &zbus::Connection) -> bool {
+    let Ok(dbus) = zbus::fdo::DBusProxy::new(conn).await else {
+        return false;
+    };
+    let Ok(name) = zbus::names::BusName::try_from(POLKIT) else {
+        return false;
+    };
+
+    if dbus.name_has_owner(name).await.unwrap_or(false) {
+        return true;
+    }
+
+    dbus.list_activatable_names().await.is_ok_and(|names| {
+        names
+            .iter()
+            .any(|activatable| activatable.as_str() == POLKIT)
+    })
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Login {
+    fn get_session(&self, session_id: &str) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait Session {
+    #[zbus(property)]
+    fn id(&self) -> zbus::Result<String>;
+}
+
+/// Remembers granted polkit actions so every click doesn't re-prompt.
+///
+/// polkit's own `auth_admin_keep` retention is five minutes, the default TTL
+/// matches it so diski never considers an action authorized for longer than
+/// polkit itself would.
+///
+/// polkit keeps that retention for the subject it was granted to, which
+/// `polkit_subject` picks. With `process` it ends when diski restarts, with
+/// `session` it covers anything else asking for the session in that time, and
+/// with `parent` it follows whatever started diski, e.g. a shell or `systemd --user`.
+///
+/// Shared between the drives' requests, the grants are only locked to be
+/// read or stored, never while polkit is asked, which can sit behind a prompt.
+pub struct Authorizer {
+    subject: Subject,
+    ttl: Duration,
+    granted: Mutex<HashMap<String, Instant>>,
+}
+
+impl Authorizer {
+    pub async fn new(
+        system: &zbus::Connection,
+        ttl: Duration,
+        subject: PolkitSubject,
+    ) -> ExResult<Self> {
+        let subject = match subject {
+            PolkitSubject::Process => Subject::new_for_owner(std::process::id(), None, None)?,
+            PolkitSubject::Parent => {
+                Subject::new_for_owner(os::unix::process::parent_id(), None, None)?
+            }
+            PolkitSubject::Session => {
+                let id = session_id(system)
+                    .await
+                    .map_err(|e| format!("could not find the login session for polkit: {e}"))?;
+                Subject {
+                    subject_kind: "unix-session".into(),
+                    subject_details: HashMap::from([(
+                        "session-id".to_owned(),
+                        OwnedValue::from(Str::from(id)),
+                    )]),
+                }
+            }
+        };
+        Ok(Self {
+            subject,
+            ttl,
+            granted: Mutex::default(),
+        })
+    }
+
+    pub async fn check(
+        &self,
+        authority: &AuthorityProxy<'_>,
+        action_id: &str,
+    ) -> zbus::Result<bool> {
+        if self.cached(action_id) {
+            return Ok(true);
+        }
+
+        let result = authority
+            .check_authorization(
+                &self.subject,
+                action_id,
+                &HashMap::default(),
+                CheckAuthorizationFlags::AllowUserInteraction.into(),
+                "",
+            )
+            .await?;
+
+        self.store(action_id, result.is_authorized);
+        Ok(result.is_authorized)
+    }
+
+    /// Whether the action was granted less than the TTL ago.
+    fn cached(&self, action_id: &str) -> bool {
+        let granted = self.granted.lock().unwrap_or_else(|e| e.into_inner());
+        granted
+            .get(action_id)
+            .is_some_and(|granted| granted.elapsed() < self.ttl)
+    }
+
+    fn store(&self, action_id: &str, authorized: bool) {
+        let mut granted = self.granted.lock().unwrap_or_else(|e| e.into_inner());
+        if authorized {
+            granted.insert(action_id.to_owned(), Instant::now());
+        } else {
+            granted.remove(action_id);
+        }
+    }
+}
+
+/// `$XDG_SESSION_ID`, or the session logind picks for the user when diski runs
+/// outside one, as it does under `systemd --user`.
+async fn session_id(conn: &zbus::Connection) -> zbus::Result<String> {
+    if let Some(id) = env::var("XDG_SESSION_ID").ok().filter(|id| !id.is_empty()) {
+        return Ok(id);
+    }
+    let path = LoginProxy::new(conn).await?.get_session("auto").await?;
+    SessionProxy::builder(conn)
+        .path(path)?
+        .build()
+        .await?
+        .id()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_ttl(ttl: Duration) -> Authorizer {
+        Authorizer {
+            subject: Subject::new_for_owner(std::process::id(), None, None).unwrap(),
+            ttl,
+            granted: Mutex::default(),
+        }
+    }
+
+    #[test]
+    fn grants_are_kept_for_the_ttl() {
+        let authorizer = with_ttl(Duration::from_secs(300));
+        assert!(!authorizer.cached(MANAGE_UNITS));
+        authorizer.store(MANAGE_UNITS, true);
+        assert!(authorizer.cached(MANAGE_UNITS));
+        assert!(!authorizer.cached(RELOAD_DAEMON));
+
+        // A refusal drops what was granted before
+        authorizer.store(MANAGE_UNITS, false);
+        assert!(!authorizer.cached(MANAGE_UNITS));
+
+        let expired = with_ttl(Duration::ZERO);
+        expired.store(MANAGE_UNITS, true);
+        assert!(!expired.cached(MANAGE_UNITS));
+    }
+}
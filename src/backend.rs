@@ -0,0 +1,612 @@
+//! The systemd manager calls the state loop, [`crate::Units`] and
+//! [`crate::job_wait`] are built on, behind [`SystemdBackend`] so tests can
+//! drive them without a bus.
+
+use futures::{Stream, StreamExt};
+use zbus::{proxy::CacheProperties, zvariant::OwnedObjectPath};
+use zbus_systemd::systemd1::{JobProxy, ManagerProxy, MountProxy, SwapProxy, UnitProxy};
+
+use crate::{config::UnitKind, MountInfo};
+
+/// A `JobNew` signal's arguments.
+#[derive(Debug, Clone)]
+pub struct JobNew {
+    pub job: OwnedObjectPath,
+    pub unit: String,
+}
+
+/// A `JobRemoved` signal's arguments.
+#[derive(Debug, Clone)]
+pub struct JobRemoved {
+    pub job: OwnedObjectPath,
+    pub unit: String,
+    pub result: String,
+}
+
+pub trait SystemdBackend {
+    /// A loaded unit the other calls act on.
+    type Unit: Clone;
+
+    async fn get_unit(&self, name: &str) -> zbus::Result<Self::Unit>;
+
+    /// Also hands out units systemd has no file for, with a `not-found` load state.
+    async fn load_unit(&self, name: &str) -> zbus::Result<Self::Unit>;
+
+    async fn load_state(&self, unit: &Self::Unit) -> zbus::Result<String>;
+
+    /// The unit's primary name, the one job signals carry.
+    async fn unit_id(&self, unit: &Self::Unit) -> zbus::Result<String>;
+
+    async fn description(&self, unit: &Self::Unit) -> zbus::Result<String>;
+
+    /// What the mount or swap unit is configured with, `None` for what it leaves unset.
+    async fn mount_info(&self, unit: &Self::Unit, kind: UnitKind) -> MountInfo;
+
+    async fn unit_file_state(&self, name: &str) -> zbus::Result<String>;
+
+    /// Asks for job and unit signals, which systemd only sends to subscribers.
+    async fn subscribe(&self) -> zbus::Result<()>;
+
+    /// Read from systemd itself, never from a cache that may have gone stale.
+    async fn sub_state(&self, unit: &Self::Unit) -> zbus::Result<String>;
+
+    async fn active_state(&self, unit: &Self::Unit) -> zbus::Result<String>;
+
+    async fn start(&self, unit: &Self::Unit, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    async fn stop(&self, unit: &Self::Unit, mode: &str) -> zbus::Result<OwnedObjectPath>;
+
+    async fn reset_failed(&self, unit: &Self::Unit) -> zbus::Result<()>;
+
+    async fn reload(&self) -> zbus::Result<()>;
+
+    /// Enables or disables the unit file, taking effect with the next reload.
+    async fn set_enabled(&self, name: &str, enabled: bool) -> zbus::Result<()>;
+
+    /// The unit's new sub-states as they are announced, ending when the
+    /// subscription is lost.
+    async fn receive_sub_state_changed(
+        &self,
+        unit: &Self::Unit,
+    ) -> impl Stream<Item = zbus::Result<String>> + Unpin + Send + 'static;
+
+    async fn receive_job_new(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = zbus::Result<JobNew>> + Unpin + Send + 'static>;
+
+    async fn receive_job_removed(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = zbus::Result<JobRemoved>> + Unpin + Send + 'static>;
+
+    /// `true` as a daemon reload starts and `false` once it finished.
+    async fn receive_reloading(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = zbus::Result<bool>> + Unpin + Send + 'static>;
+
+    /// `start` or `stop` for the jobs diski queues, an error once the job is gone.
+    async fn job_type(&self, job: &OwnedObjectPath) -> zbus::Result<String>;
+
+    /// Errors count as pending, the signal or the timeout will settle it.
+    async fn job_pending(&self, job: &OwnedObjectPath) -> bool;
+}
+
+impl SystemdBackend for ManagerProxy<'_> {
+    type Unit = UnitProxy<'static>;
+
+    async fn get_unit(&self, name: &str) -> zbus::Result<Self::Unit> {
+        let path = ManagerProxy::get_unit(self, name.to_owned()).await?;
+        UnitProxy::new(self.inner().connection(), path).await
+    }
+
+    async fn load_unit(&self, name: &str) -> zbus::Result<Self::Unit> {
+        let path = ManagerProxy::load_unit(self, name.to_owned()).await?;
+        UnitProxy::new(self.inner().connection(), path).await
+    }
+
+    async fn load_state(&self, unit: &Self::Unit) -> zbus::Result<String> {
+        uncached(unit).await?.load_state().await
+    }
+
+    async fn unit_id(&self, unit: &Self::Unit) -> zbus::Result<String> {
+        unit.id().await
+    }
+
+    async fn description(&self, unit: &Self::Unit) -> zbus::Result<String> {
+        unit.description().await
+    }
+
+    async fn mount_info(&self, unit: &Self::Unit, kind: UnitKind) -> MountInfo {
+        let non_empty = |value: zbus::Result<String>| value.ok().filter(|v| !v.is_empty());
+        let (conn, path) = (unit.inner().connection(), unit.inner().path().to_owned());
+
+        match kind {
+            UnitKind::Mount => {
+                let mount = async {
+                    MountProxy::builder(conn)
+                        .path(path)?
+                        .cache_properties(CacheProperties::No)
+                        .build()
+                        .await
+                };
+                let Ok(mount) = mount.await else {
+                    return MountInfo::default();
+                };
+                MountInfo {
+                    path: non_empty(mount.where_property().await),
+                    fs_type: non_empty(mount.type_property().await),
+                    what: non_empty(mount.what().await),
+                    options: non_empty(mount.options().await),
+                }
+            }
+            UnitKind::Swap => {
+                let swap = async {
+                    SwapProxy::builder(conn)
+                        .path(path)?
+                        .cache_properties(CacheProperties::No)
+                        .build()
+                        .await
+                };
+                let Ok(swap) = swap.await else {
+                    return MountInfo::default();
+                };
+                MountInfo {
+                    what: non_empty(swap.what().await),
+                    options: non_empty(swap.options().await),
+                    ..MountInfo::default()
+                }
+            }
+        }
+    }
+
+    async fn unit_file_state(&self, name: &str) -> zbus::Result<String> {
+        self.get_unit_file_state(name.to_owned()).await
+    }
+
+    async fn subscribe(&self) -> zbus::Result<()> {
+        ManagerProxy::subscribe(self).await
+    }
+
+    async fn sub_state(&self, unit: &Self::Unit) -> zbus::Result<String> {
+        uncached(unit).await?.sub_state().await
+    }
+
+    async fn active_state(&self, unit: &Self::Unit) -> zbus::Result<String> {
+        uncached(unit).await?.active_state().await
+    }
+
+    async fn start(&self, unit: &Self::Unit, mode: &str) -> zbus::Result<OwnedObjectPath> {
+        unit.start(mode.to_owned()).await
+    }
+
+    async fn stop(&self, unit: &Self::Unit, mode: &str) -> zbus::Result<OwnedObjectPath> {
+        unit.stop(mode.to_owned()).await
+    }
+
+    async fn reset_failed(&self, unit: &Self::Unit) -> zbus::Result<()> {
+        unit.reset_failed().await
+    }
+
+    async fn reload(&self) -> zbus::Result<()> {
+        ManagerProxy::reload(self).await
+    }
+
+    async fn set_enabled(&self, name: &str, enabled: bool) -> zbus::Result<()> {
+        if enabled {
+            self.enable_unit_files(vec![name.to_owned()], false, false)
+                .await?;
+        } else {
+            self.disable_unit_files(vec![name.to_owned()], false)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn receive_sub_state_changed(
+        &self,
+        unit: &Self::Unit,
+    ) -> impl Stream<Item = zbus::Result<String>> + Unpin + Send + 'static {
+        unit.receive_sub_state_changed()
+            .await
+            .then(|change| async move { change.get().await })
+            .boxed()
+    }
+
+    async fn receive_job_new(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = zbus::Result<JobNew>> + Unpin + Send + 'static> {
+        let news = ManagerProxy::receive_job_new(self).await?;
+        Ok(news.map(|new| {
+            let args = new.args()?;
+            Ok(JobNew {
+                job: args.job.clone(),
+                unit: args.unit.clone(),
+            })
+        }))
+    }
+
+    async fn receive_job_removed(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = zbus::Result<JobRemoved>> + Unpin + Send + 'static> {
+        let removals = ManagerProxy::receive_job_removed(self).await?;
+        Ok(removals.map(|removed| {
+            let args = removed.args()?;
+            Ok(JobRemoved {
+                job: args.job.clone(),
+                unit: args.unit.clone(),
+                result: args.result.clone(),
+            })
+        }))
+    }
+
+    async fn receive_reloading(
+        &self,
+    ) -> zbus::Result<impl Stream<Item = zbus::Result<bool>> + Unpin + Send + 'static> {
+        let reloads = ManagerProxy::receive_reloading(self).await?;
+        Ok(reloads.map(|reload| Ok(reload.args()?.active)))
+    }
+
+    async fn job_type(&self, job: &OwnedObjectPath) -> zbus::Result<String> {
+        JobProxy::builder(self.inner().connection())
+            .path(job.clone())?
+            .build()
+            .await?
+            .job_type()
+            .await
+    }
+
+    async fn job_pending(&self, job: &OwnedObjectPath) -> bool {
+        match self.list_jobs().await {
+            Ok(jobs) => jobs.iter().any(|(_, _, _, _, path, _)| path == job),
+            Err(e) => {
+                tracing::debug!("Could not list jobs: {e}");
+                true
+            }
+        }
+    }
+}
+
+/// The unit's cached properties stop being updated once its signals do.
+async fn uncached(unit: &UnitProxy<'static>) -> zbus::Result<UnitProxy<'static>> {
+    UnitProxy::builder(unit.inner().connection())
+        .path(unit.inner().path().to_owned())?
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use futures::{channel::mpsc, Stream};
+    use zbus::zvariant::OwnedObjectPath;
+
+    use super::{JobNew, JobRemoved, SystemdBackend};
+    use crate::{config::UnitKind, MountInfo};
+
+    /// What the next started or stopped job does.
+    #[derive(Debug, Clone)]
+    pub enum Outcome {
+        /// Removed with this result, the signal sent before the job's path is
+        /// even returned, as systemd can for jobs with nothing to do.
+        Removed(&'static str),
+        /// Leaves the queue without a `JobRemoved` signal, the unit left in
+        /// this active state.
+        Vanished(&'static str),
+        /// Stays queued until [`MockSystemd::finish_jobs`].
+        Pending,
+    }
+
+    struct Unit {
+        sub_state: String,
+        active_state: String,
+        unit_file_state: String,
+        watchers: Vec<mpsc::UnboundedSender<zbus::Result<String>>>,
+    }
+
+    impl Unit {
+        fn set_sub_state(&mut self, sub_state: &str, signals: bool) {
+            self.sub_state = sub_state.to_owned();
+            if signals {
+                self.watchers
+                    .retain(|watcher| watcher.unbounded_send(Ok(sub_state.to_owned())).is_ok());
+            }
+        }
+    }
+
+    struct State {
+        units: HashMap<String, Unit>,
+        job_news: Vec<mpsc::UnboundedSender<zbus::Result<JobNew>>>,
+        removals: Vec<mpsc::UnboundedSender<zbus::Result<JobRemoved>>>,
+        reloads: Vec<mpsc::UnboundedSender<zbus::Result<bool>>>,
+        /// Every job queued so far, with its unit and type.
+        job_types: HashMap<OwnedObjectPath, (String, &'static str)>,
+        pending: Vec<OwnedObjectPath>,
+        jobs: u32,
+        outcome: Outcome,
+        signals: bool,
+    }
+
+    impl State {
+        /// Leaves the unit where a finished job with this result would, then
+        /// announces the removal.
+        fn remove(&mut self, job: OwnedObjectPath, result: &str) {
+            let (name, job_type) = self.job_types[&job].clone();
+            let (active_state, sub_state) = match (result, job_type) {
+                ("done", "start") if name.ends_with(".automount") => ("active", "waiting"),
+                ("done", "start") => ("active", "mounted"),
+                ("done", _) => ("inactive", "dead"),
+                _ => ("failed", "failed"),
+            };
+            let signals = self.signals;
+            let unit = self
+                .units
+                .get_mut(&name)
+                .expect("only loaded units get jobs");
+            unit.active_state = active_state.to_owned();
+            unit.set_sub_state(sub_state, signals);
+
+            let removed = JobRemoved {
+                job,
+                unit: name,
+                result: result.to_owned(),
+            };
+            self.removals
+                .retain(|watcher| watcher.unbounded_send(Ok(removed.clone())).is_ok());
+        }
+    }
+
+    /// A systemd manager in memory, with units added and changed by the test.
+    #[derive(Clone)]
+    pub struct MockSystemd {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl Default for MockSystemd {
+        fn default() -> Self {
+            Self {
+                state: Arc::new(Mutex::new(State {
+                    units: HashMap::new(),
+                    job_news: Vec::new(),
+                    removals: Vec::new(),
+                    reloads: Vec::new(),
+                    job_types: HashMap::new(),
+                    pending: Vec::new(),
+                    jobs: 0,
+                    outcome: Outcome::Removed("done"),
+                    signals: true,
+                })),
+            }
+        }
+    }
+
+    impl MockSystemd {
+        pub fn add_unit(&self, name: &str, sub_state: &str, active_state: &str) {
+            self.state.lock().unwrap().units.insert(
+                name.to_owned(),
+                Unit {
+                    sub_state: sub_state.to_owned(),
+                    active_state: active_state.to_owned(),
+                    unit_file_state: "disabled".to_owned(),
+                    watchers: Vec::new(),
+                },
+            );
+        }
+
+        /// Changes the sub-state, announcing it unless signals are off.
+        pub fn set_sub_state(&self, name: &str, sub_state: &str) {
+            let mut state = self.state.lock().unwrap();
+            let signals = state.signals;
+            let unit = state.units.get_mut(name).expect("unit added first");
+            unit.set_sub_state(sub_state, signals);
+        }
+
+        /// Stops announcing sub-states, as when systemd refused the subscription.
+        pub fn set_signals(&self, signals: bool) {
+            self.state.lock().unwrap().signals = signals;
+        }
+
+        /// Ends every sub-state stream of the unit, as a dropped match rule would.
+        pub fn end_sub_state_streams(&self, name: &str) {
+            let mut state = self.state.lock().unwrap();
+            state
+                .units
+                .get_mut(name)
+                .expect("unit added first")
+                .watchers
+                .clear();
+        }
+
+        pub fn set_outcome(&self, outcome: Outcome) {
+            self.state.lock().unwrap().outcome = outcome;
+        }
+
+        /// Removes the jobs left queued by [`Outcome::Pending`] with this result.
+        pub fn finish_jobs(&self, result: &str) {
+            let mut state = self.state.lock().unwrap();
+            for job in std::mem::take(&mut state.pending) {
+                state.remove(job, result);
+            }
+        }
+
+        fn queue(&self, name: &str, job_type: &'static str) -> zbus::Result<OwnedObjectPath> {
+            let mut state = self.state.lock().unwrap();
+            if !state.units.contains_key(name) {
+                return Err(zbus::Error::Failure(format!("{name} not loaded")));
+            }
+            state.jobs += 1;
+            let job =
+                OwnedObjectPath::try_from(format!("/org/freedesktop/systemd1/job/{}", state.jobs))
+                    .expect("a valid object path");
+            state
+                .job_types
+                .insert(job.clone(), (name.to_owned(), job_type));
+
+            let new = JobNew {
+                job: job.clone(),
+                unit: name.to_owned(),
+            };
+            state
+                .job_news
+                .retain(|watcher| watcher.unbounded_send(Ok(new.clone())).is_ok());
+
+            match state.outcome.clone() {
+                Outcome::Removed(result) => state.remove(job.clone(), result),
+                Outcome::Vanished(active_state) => {
+                    state
+                        .units
+                        .get_mut(name)
+                        .expect("checked above")
+                        .active_state = active_state.to_owned();
+                }
+                Outcome::Pending => state.pending.push(job.clone()),
+            }
+            Ok(job)
+        }
+    }
+
+    impl SystemdBackend for MockSystemd {
+        type Unit = String;
+
+        async fn get_unit(&self, name: &str) -> zbus::Result<Self::Unit> {
+            match self.state.lock().unwrap().units.contains_key(name) {
+                true => Ok(name.to_owned()),
+                false => Err(zbus::Error::Failure(format!("{name} not loaded"))),
+            }
+        }
+
+        async fn load_unit(&self, name: &str) -> zbus::Result<Self::Unit> {
+            Ok(name.to_owned())
+        }
+
+        async fn load_state(&self, unit: &Self::Unit) -> zbus::Result<String> {
+            Ok(match self.state.lock().unwrap().units.contains_key(unit) {
+                true => "loaded".to_owned(),
+                false => "not-found".to_owned(),
+            })
+        }
+
+        async fn unit_id(&self, unit: &Self::Unit) -> zbus::Result<String> {
+            Ok(unit.clone())
+        }
+
+        async fn description(&self, unit: &Self::Unit) -> zbus::Result<String> {
+            Ok(unit.clone())
+        }
+
+        async fn mount_info(&self, _unit: &Self::Unit, _kind: UnitKind) -> MountInfo {
+            MountInfo::default()
+        }
+
+        async fn unit_file_state(&self, name: &str) -> zbus::Result<String> {
+            match self.state.lock().unwrap().units.get(name) {
+                Some(unit) => Ok(unit.unit_file_state.clone()),
+                None => Err(zbus::Error::Failure(format!("no unit file for {name}"))),
+            }
+        }
+
+        async fn subscribe(&self) -> zbus::Result<()> {
+            Ok(())
+        }
+
+        async fn sub_state(&self, unit: &Self::Unit) -> zbus::Result<String> {
+            Ok(self.state.lock().unwrap().units[unit].sub_state.clone())
+        }
+
+        async fn active_state(&self, unit: &Self::Unit) -> zbus::Result<String> {
+            Ok(self.state.lock().unwrap().units[unit].active_state.clone())
+        }
+
+        async fn start(&self, unit: &Self::Unit, _mode: &str) -> zbus::Result<OwnedObjectPath> {
+            self.queue(unit, "start")
+        }
+
+        async fn stop(&self, unit: &Self::Unit, _mode: &str) -> zbus::Result<OwnedObjectPath> {
+            self.queue(unit, "stop")
+        }
+
+        async fn reset_failed(&self, unit: &Self::Unit) -> zbus::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            let signals = state.signals;
+            let unit = state.units.get_mut(unit).expect("unit added first");
+            if unit.active_state == "failed" {
+                unit.active_state = "inactive".to_owned();
+                unit.set_sub_state("dead", signals);
+            }
+            Ok(())
+        }
+
+        async fn reload(&self) -> zbus::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            for active in [true, false] {
+                state
+                    .reloads
+                    .retain(|watcher| watcher.unbounded_send(Ok(active)).is_ok());
+            }
+            Ok(())
+        }
+
+        async fn set_enabled(&self, name: &str, enabled: bool) -> zbus::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            let unit = state.units.get_mut(name).expect("unit added first");
+            unit.unit_file_state = if enabled { "enabled" } else { "disabled" }.to_owned();
+            Ok(())
+        }
+
+        async fn receive_sub_state_changed(
+            &self,
+            unit: &Self::Unit,
+        ) -> impl Stream<Item = zbus::Result<String>> + Unpin + Send + 'static {
+            let (sender, receiver) = mpsc::unbounded();
+            let mut state = self.state.lock().unwrap();
+            state
+                .units
+                .get_mut(unit)
+                .expect("unit added first")
+                .watchers
+                .push(sender);
+            receiver
+        }
+
+        async fn receive_job_new(
+            &self,
+        ) -> zbus::Result<impl Stream<Item = zbus::Result<JobNew>> + Unpin + Send + 'static>
+        {
+            let (sender, receiver) = mpsc::unbounded();
+            self.state.lock().unwrap().job_news.push(sender);
+            Ok(receiver)
+        }
+
+        async fn receive_job_removed(
+            &self,
+        ) -> zbus::Result<impl Stream<Item = zbus::Result<JobRemoved>> + Unpin + Send + 'static>
+        {
+            let (sender, receiver) = mpsc::unbounded();
+            self.state.lock().unwrap().removals.push(sender);
+            Ok(receiver)
+        }
+
+        async fn receive_reloading(
+            &self,
+        ) -> zbus::Result<impl Stream<Item = zbus::Result<bool>> + Unpin + Send + 'static> {
+            let (sender, receiver) = mpsc::unbounded();
+            self.state.lock().unwrap().reloads.push(sender);
+            Ok(receiver)
+        }
+
+        async fn job_type(&self, job: &OwnedObjectPath) -> zbus::Result<String> {
+            let state = self.state.lock().unwrap();
+            match state.job_types.get(job) {
+                Some((_, job_type)) if state.pending.contains(job) => Ok((*job_type).to_owned()),
+                _ => Err(zbus::Error::Failure(format!("{} is gone", job.as_str()))),
+            }
+        }
+
+        async fn job_pending(&self, job: &OwnedObjectPath) -> bool {
+            self.state.lock().unwrap().pending.contains(job)
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! The command line, parsed once up front and handed to whatever it asks for.
+
+use clap::{Parser, Subcommand};
+
+use crate::{config, diagnose, install, mount, status};
+
+const ABOUT: &str =
+    "Shows a tray icon for mounting and unmounting drives through their systemd units.";
+
+const AFTER_HELP: &str = "\
+Drives are read from $XDG_CONFIG_HOME/diski/config.toml or
+~/.config/diski/config.toml, the first that exists, or the file given with
+--config, otherwise from the arguments.
+
+`install` writes a systemd user service starting diski for the drive with
+the graphical session, or an XDG autostart entry with --autostart, and
+--enable enables and starts the service. `uninstall` removes either.
+
+`diagnose` prints the drive's units, their states and properties, its
+device and the bus diski talks to, for bug reports. --redact-serials leaves
+the drive's serial number out.
+
+`status`, `watch` and `mount` exit with 2 when the drive's units don't
+exist, 3 when the request wasn't authorized, 4 when waiting timed out and 1
+on any other failure.
+
+Clicking a tray icon opens its menu, unless on_activate in the config file
+is set to \"toggle_mount\" or \"disconnect\". Middle-clicking it (the secondary
+activation, which some hosts map to another gesture or not at all) mounts
+the drive when it isn't mounted and unmounts it when it is.";
+
+#[derive(Debug, Parser)]
+#[command(version, about = ABOUT, after_help = AFTER_HELP, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Without a subcommand, the tray is run.
+    #[command(flatten)]
+    pub tray: config::Args,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print the drive's unit states once
+    Status(status::Args),
+    /// Print the drive's unit states as they change
+    Watch(status::Args),
+    /// Start the drive's mount unit
+    Mount(mount::Args),
+    /// Print what diski sees of the drive and its bus, for bug reports
+    Diagnose(diagnose::Args),
+    /// Start diski for the drive at login
+    Install(install::InstallArgs),
+    /// Remove what `install` wrote
+    Uninstall(install::UninstallArgs),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(["diski"].iter().chain(args))
+    }
+
+    #[test]
+    fn drives_are_not_mistaken_for_subcommands() {
+        let cli = parse(&["--user", "mnt-backup", "Backup"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(cli.tray.user);
+        assert_eq!(cli.tray.name.as_deref(), Some("mnt-backup"));
+        assert_eq!(cli.tray.display_name.as_deref(), Some("Backup"));
+
+        let cli = parse(&["status", "--json", "mnt-backup"]).unwrap();
+        assert!(
+            matches!(cli.command, Some(Command::Status(args)) if args.json && args.name == "mnt-backup")
+        );
+    }
+
+    #[test]
+    fn flags_only_apply_where_they_are_given() {
+        // A value that looks like a flag is neither taken nor answered
+        let e = parse(&["install", "--name", "-V"]).unwrap_err();
+        assert_ne!(e.kind(), clap::error::ErrorKind::DisplayVersion);
+        assert_eq!(
+            parse(&["-V"]).unwrap_err().kind(),
+            clap::error::ErrorKind::DisplayVersion
+        );
+
+        assert!(parse(&["status", "--no-tray", "mnt-backup"]).is_err());
+        assert!(parse(&["--mount", "/mnt/backup", "--device", "/dev/sdb1"]).is_err());
+    }
+
+    #[test]
+    fn subcommands_take_a_bus_address() {
+        let address = "unix:path=/run/nested/bus";
+        for command in ["status", "watch", "mount", "diagnose"] {
+            let cli = parse(&[command, "--bus-address", address, "mnt-backup"]).unwrap();
+            let given = match cli.command {
+                Some(Command::Status(args) | Command::Watch(args)) => args.bus_address,
+                Some(Command::Mount(args)) => args.bus_address,
+                Some(Command::Diagnose(args)) => args.bus_address,
+                _ => None,
+            };
+            assert_eq!(
+                given.map(|a| a.to_string()).as_deref(),
+                Some(address),
+                "{command}"
+            );
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::process::Stdio;
+
+use tokio::{io::AsyncWriteExt, process::Command};
+
+/// Hands `text` to `wl-copy` under Wayland and `xclip` otherwise, the tools
+/// keep serving the selection after diski's part is done.
+pub async fn copy(text: &str) -> Result<(), String> {
+    let (program, args): (&str, &[&str]) = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        ("wl-copy", &[])
+    } else {
+        ("xclip", &["-selection", "clipboard"])
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run {program}: {e}"))?;
+
+    let mut stdin = child.stdin.take().ok_or("no pipe to write to")?;
+    stdin
+        .write_all(text.as_bytes())
+        .await
+        .map_err(|e| format!("could not write to {program}: {e}"))?;
+    drop(stdin);
+
+    let status = child
+        .wait()
+        .await
+        .map_err(|e| format!("could not wait for {program}: {e}"))?;
+    if !status.success() {
+        return Err(format!("{program} exited with {status}"));
+    }
+    Ok(())
+}
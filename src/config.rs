@@ -0,0 +1,1204 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    env, fmt, fs, io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use toml_edit::{ImDocument, Item, Table};
+use tracing::info;
+
+use crate::{fstab, unit_name};
+
+#[derive(Debug)]
+pub struct Config {
+    pub drives: Vec<DriveSpec>,
+    pub job_timeout: Duration,
+    pub auth_cache_ttl: Duration,
+    pub instance: Instance,
+    /// Connect to this bus instead of the instance's usual one.
+    pub bus_address: Option<zbus::Address>,
+    /// Command the mount point is appended to when opening it.
+    pub opener: String,
+    /// Run without a tray icon, controlled through the socket instead.
+    pub tray: bool,
+    pub sync_before_unmount: bool,
+    /// Ask before acting on the menu's disconnect item.
+    pub confirm_disconnect: bool,
+    /// Power removable drives off once they are fully disconnected.
+    pub power_off_on_disconnect: bool,
+    /// How many more times a failed mount is tried before giving up.
+    pub mount_retries: u32,
+    /// Treat every action as authorized when polkit is missing, instead of refusing them.
+    pub allow_without_polkit: bool,
+    /// Who polkit is asked to authorize, and so what its `auth_admin_keep` retention sticks to.
+    pub polkit_subject: PolkitSubject,
+    /// Where to serve OpenMetrics, off unless given.
+    pub metrics_addr: Option<SocketAddr>,
+    /// Log what requests would do instead of doing it.
+    pub dry_run: bool,
+    /// How often unit states are read when systemd won't send signals.
+    pub poll_interval: Duration,
+    /// How often free space is read for every mounted drive at once.
+    pub space_interval: Duration,
+    /// How long notifications stay up, zero until dismissed and `None` as long
+    /// as the notification server decides.
+    pub notification_timeout: Option<Duration>,
+    /// Show a notification for requests that succeeded, failures always are.
+    /// The tray's quiet toggle overrides it per drive once used.
+    pub notify_on_success: bool,
+    /// Icon names for notifications about successes and failures.
+    pub notify_icon_success: String,
+    pub notify_icon_error: String,
+    /// Play the sound theme's `complete` and `dialog-warning` sounds with them.
+    pub notify_sound: bool,
+    /// Share one tray icon between the mounts of a physical drive.
+    pub group_partitions: bool,
+    /// Grouped icons always open their menu.
+    pub on_activate: OnActivate,
+    /// Mark access keys in menu labels with `_`, for keyboard navigation.
+    /// KDE and the GNOME AppIndicator extension read them, hosts that don't
+    /// show the underscore instead, so it is off by default.
+    pub menu_mnemonics: bool,
+    pub menu: MenuLayout,
+    /// Hold off sleep while a request runs, and sync mounted drives before suspending.
+    pub inhibit_sleep: bool,
+    /// After enabling automounting, open the mount point and wait for it to mount.
+    pub verify_automount: bool,
+    pub hooks: Hooks,
+    pub icons: Icons,
+}
+
+/// Which systemd manager the drives' units live in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instance {
+    System,
+    User,
+}
+
+impl Instance {
+    /// From a `--user` flag.
+    pub fn from_user(user: bool) -> Self {
+        if user {
+            Self::User
+        } else {
+            Self::System
+        }
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::System => write!(f, "system"),
+            Self::User => write!(f, "user"),
+        }
+    }
+}
+
+/// What left-clicking a drive's tray icon does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnActivate {
+    Menu,
+    ToggleMount,
+    Disconnect,
+}
+
+/// The polkit subject authorization is checked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolkitSubject {
+    /// diski's own process, the default.
+    Process,
+    /// The login session diski runs in.
+    Session,
+    /// The process that started diski.
+    Parent,
+}
+
+/// The unit a drive is managed through, a mount with its automount or a swap on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitKind {
+    Mount,
+    Swap,
+}
+
+impl UnitKind {
+    /// Takes a `.mount` or `.swap` suffix off the systemd name, names without one are mounts.
+    pub fn split(name: &str) -> (String, Self) {
+        match name.strip_suffix(".swap") {
+            Some(prefix) => (prefix.to_owned(), Self::Swap),
+            None => (
+                name.strip_suffix(".mount").unwrap_or(name).to_owned(),
+                Self::Mount,
+            ),
+        }
+    }
+
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Self::Mount => "mount",
+            Self::Swap => "swap",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Icons {
+    pub mounted: String,
+    pub armed: String,
+    pub unmounted: String,
+    pub busy: String,
+    pub failed: String,
+    /// The states the `[icons]` table names, which a drive's own `icon` leaves alone.
+    pub configured: Vec<&'static str>,
+}
+
+impl Icons {
+    /// Shows `icon` in every state the `[icons]` table doesn't name.
+    pub fn apply(&mut self, icon: &str) {
+        let Self {
+            mounted,
+            armed,
+            unmounted,
+            busy,
+            failed,
+            configured,
+        } = self;
+        for (key, state) in [
+            ("mounted", mounted),
+            ("armed", armed),
+            ("unmounted", unmounted),
+            ("busy", busy),
+            ("failed", failed),
+        ] {
+            if !configured.contains(&key) {
+                icon.clone_into(state);
+            }
+        }
+    }
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Self {
+            mounted: "drive-harddisk".into(),
+            armed: "drive-harddisk-symbolic".into(),
+            unmounted: "media-eject".into(),
+            busy: "media-optical".into(),
+            failed: "dialog-error".into(),
+            configured: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DriveSpec {
+    /// Without the unit's suffix, which `kind` stands for.
+    pub systemd_name: String,
+    pub kind: UnitKind,
+    /// Falls back to the mount unit's description when not given.
+    pub display_name: Option<String>,
+    /// Unmount once the device has seen no I/O for this long.
+    pub auto_unmount_after: Option<Duration>,
+    /// How long a unit's sub-state has to settle before it is shown, zero shows every change.
+    pub debounce: Duration,
+    /// Found by `--from-fstab` rather than configured, skipped when it has no units.
+    pub discovered: bool,
+    /// The LUKS container to unlock before mounting, when it isn't the mount's `What`.
+    pub encrypted_device: Option<String>,
+    /// Warn once free space drops below this share of the drive, `None` never warns.
+    pub low_space: Option<LowSpace>,
+    /// Shown in every state `[icons]` doesn't set, a theme icon name or a file path.
+    pub icon: Option<String>,
+    /// Comma separated options the mount should have, warned about when it doesn't.
+    pub mount_options: Option<String>,
+}
+
+/// The actions `menu_order` can name, in the order the menu has them by default.
+pub const MENU_ACTIONS: &[&str] = &[
+    "open-folder",
+    "copy-path",
+    "disconnect",
+    "mount",
+    "unmount",
+    "remount",
+    "read-only",
+    "automount",
+    "automount-at-boot",
+    "quiet",
+];
+
+/// What a drive's menu leaves out, and which actions it puts first.
+#[derive(Debug, Clone)]
+pub struct MenuLayout {
+    /// The automount state line and both automount checkmarks.
+    pub show_automount_item: bool,
+    /// The informative lines about the drive, alerts and warnings always show.
+    pub show_state_items: bool,
+    /// The mount point line, even with the other state lines shown.
+    pub show_path_item: bool,
+    /// The version line at the bottom, which opens the project's page.
+    pub show_about_item: bool,
+    /// Names from [`MENU_ACTIONS`] that come first in this order, the rest follow as usual.
+    pub order: Vec<String>,
+}
+
+impl Default for MenuLayout {
+    fn default() -> Self {
+        Self {
+            show_automount_item: true,
+            show_state_items: true,
+            show_path_item: true,
+            show_about_item: true,
+            order: Vec::new(),
+        }
+    }
+}
+
+/// Commands run when a drive's mount unit changes state, see [`crate::hooks`].
+#[derive(Debug, Clone)]
+pub struct Hooks {
+    /// `hooks = false` or `--no-hooks` turns every one of them off.
+    pub enabled: bool,
+    pub on_mounted: Option<String>,
+    pub on_unmounted: Option<String>,
+    pub on_failed: Option<String>,
+    /// Hooks still running after this long are killed.
+    pub timeout: Duration,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_mounted: None,
+            on_unmounted: None,
+            on_failed: None,
+            timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LowSpace {
+    pub percent: u32,
+    /// The least time between checks, made on the shared `space_interval` reads.
+    pub interval: Duration,
+}
+
+impl Default for LowSpace {
+    fn default() -> Self {
+        Self {
+            percent: 10,
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(150);
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The options of the tray, see [`Config::load`].
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Use the user's systemd instance instead of the system one
+    #[arg(long, visible_alias = "session")]
+    pub user: bool,
+    /// Run without a tray icon, controlled through the socket
+    #[arg(long)]
+    pub no_tray: bool,
+    /// Add every drive fstab mounts on demand
+    #[arg(long)]
+    pub from_fstab: bool,
+    /// Log what requests would do instead of doing it
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Don't run on_mounted, on_unmounted or on_failed
+    #[arg(long)]
+    pub no_hooks: bool,
+    /// Serve OpenMetrics on http://<address>/metrics
+    #[arg(long, value_name = "address")]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Read the config file at <path>
+    #[arg(long, value_name = "path")]
+    pub config: Option<PathBuf>,
+    /// Connect to this D-Bus address, like unix:path=/run/dbus/system_bus_socket
+    #[arg(long, value_name = "address")]
+    pub bus_address: Option<zbus::Address>,
+    /// Name the drive by where it is mounted
+    #[arg(long, value_name = "path", conflicts_with = "device")]
+    pub mount: Option<String>,
+    /// Name the drive by the device fstab mounts
+    #[arg(long, value_name = "path")]
+    pub device: Option<String>,
+    /// Without a config file, the drive to show
+    #[arg(value_name = "systemd name")]
+    pub name: Option<String>,
+    /// What the tray calls it, the mount unit's description otherwise
+    #[arg(value_name = "display name")]
+    pub display_name: Option<String>,
+}
+
+impl Config {
+    /// Falls back to the `<systemd name> [display name]` arguments when no config file exists,
+    /// where `--mount <path>` or `--device <path>` can stand in for the systemd name.
+    /// The flags apply regardless of the file.
+    pub fn load(args: Args) -> Result<Self, ConfigError> {
+        let Args {
+            user,
+            no_tray,
+            from_fstab,
+            dry_run,
+            no_hooks,
+            metrics_addr,
+            config: config_path,
+            bus_address,
+            mount,
+            device,
+            name,
+            display_name,
+        } = args;
+        let mut positional: Vec<String> = name.into_iter().chain(display_name).collect();
+        if mount.is_some() || device.is_some() {
+            positional.insert(0, systemd_name(mount, device)?);
+        }
+
+        let mut config = Self::load_file(positional.into_iter(), from_fstab, config_path)?;
+
+        if from_fstab {
+            config.discover_drives()?;
+            if config.drives.is_empty() {
+                return Err(ConfigError::NoDrives);
+            }
+        }
+
+        if user {
+            config.instance = Instance::User;
+        }
+        if no_tray {
+            config.tray = false;
+        }
+        config.dry_run = dry_run;
+        if no_hooks {
+            config.hooks.enabled = false;
+        }
+        if metrics_addr.is_some() {
+            config.metrics_addr = metrics_addr;
+        }
+        if bus_address.is_some() {
+            config.bus_address = bus_address;
+        }
+
+        // zbus reads these itself, but only reports a malformed one when
+        // connecting, which is retried forever
+        if config.bus_address.is_none() {
+            let var = match config.instance {
+                Instance::System => "DBUS_SYSTEM_BUS_ADDRESS",
+                Instance::User => "DBUS_SESSION_BUS_ADDRESS",
+            };
+            if let Ok(value) = env::var(var) {
+                value
+                    .parse::<zbus::Address>()
+                    .map_err(|e| ConfigError::InvalidAddress(format!("${var}"), e))?;
+            }
+        }
+
+        if let Ok(timeout) = env::var("DISKI_JOB_TIMEOUT") {
+            config.job_timeout = parse_duration(&timeout)
+                .filter(|timeout| !timeout.is_zero())
+                .ok_or(ConfigError::InvalidEnv("DISKI_JOB_TIMEOUT"))?;
+        }
+
+        Ok(config)
+    }
+
+    /// An explicit `path` has to exist, the default locations are tried in
+    /// order and skipped when there is nothing there.
+    fn load_file(
+        mut args: impl Iterator<Item = String>,
+        from_fstab: bool,
+        path: Option<PathBuf>,
+    ) -> Result<Self, ConfigError> {
+        if let Some(path) = path {
+            let source = fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+            let config = Self::parse(&path, source, from_fstab)?;
+            info!("Loaded the configuration from {}", path.display());
+            return Ok(config);
+        }
+
+        for path in default_paths() {
+            match fs::read_to_string(&path) {
+                Ok(source) => {
+                    let config = Self::parse(&path, source, from_fstab)?;
+                    info!("Loaded the configuration from {}", path.display());
+                    return Ok(config);
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(ConfigError::Io(path, e)),
+            }
+        }
+
+        match (args.next(), args.next()) {
+            (Some(name), display_name) => {
+                let (systemd_name, kind) = UnitKind::split(&name);
+                Ok(Self::with_drives(vec![DriveSpec {
+                    systemd_name,
+                    kind,
+                    display_name,
+                    auto_unmount_after: None,
+                    debounce: DEFAULT_DEBOUNCE,
+                    discovered: false,
+                    encrypted_device: None,
+                    low_space: Some(LowSpace::default()),
+                    icon: None,
+                    mount_options: None,
+                }]))
+            }
+            (None, _) if from_fstab => Ok(Self::with_drives(Vec::new())),
+            _ => Err(ConfigError::NoDrives),
+        }
+    }
+
+    /// Adds every on-demand fstab entry that isn't configured already.
+    fn discover_drives(&mut self) -> Result<(), ConfigError> {
+        let entries = fstab::read().map_err(|e| ConfigError::Io("/etc/fstab".into(), e))?;
+
+        for entry in entries {
+            if entry.fs_type == "swap" || !entry.is_on_demand() {
+                continue;
+            }
+
+            let systemd_name = unit_name::from_mount_point(&entry.mount_point);
+            if self.drives.iter().any(|d| d.systemd_name == systemd_name) {
+                continue;
+            }
+
+            self.drives.push(DriveSpec {
+                systemd_name,
+                kind: UnitKind::Mount,
+                display_name: None,
+                auto_unmount_after: None,
+                debounce: DEFAULT_DEBOUNCE,
+                discovered: true,
+                encrypted_device: None,
+                low_space: Some(LowSpace::default()),
+                icon: None,
+                mount_options: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn with_drives(drives: Vec<DriveSpec>) -> Self {
+        Self {
+            drives,
+            job_timeout: Duration::from_secs(60),
+            auth_cache_ttl: Duration::from_secs(5 * 60),
+            instance: Instance::System,
+            bus_address: None,
+            opener: "xdg-open".into(),
+            tray: true,
+            sync_before_unmount: true,
+            confirm_disconnect: false,
+            power_off_on_disconnect: false,
+            mount_retries: 0,
+            allow_without_polkit: false,
+            polkit_subject: PolkitSubject::Process,
+            metrics_addr: None,
+            dry_run: false,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            space_interval: Duration::from_secs(30),
+            notification_timeout: None,
+            notify_on_success: true,
+            notify_icon_success: "drive-harddisk".into(),
+            notify_icon_error: "dialog-warning".into(),
+            notify_sound: false,
+            group_partitions: false,
+            menu_mnemonics: false,
+            menu: MenuLayout::default(),
+            inhibit_sleep: false,
+            verify_automount: false,
+            hooks: Hooks::default(),
+            on_activate: OnActivate::Menu,
+            icons: Icons::default(),
+        }
+    }
+
+    fn parse(path: &Path, source: String, from_fstab: bool) -> Result<Self, ConfigError> {
+        let doc = ImDocument::parse(source.as_str())
+            .map_err(|e| ConfigError::Syntax(path.to_owned(), e))?;
+        let root = Section::new(path, &source, doc.as_table());
+        let debounce = root.duration("debounce")?.unwrap_or(DEFAULT_DEBOUNCE);
+        let low_space = root.low_space(LowSpace::default())?;
+
+        let mut drives = Vec::new();
+        match root.get("drive") {
+            None => {}
+            Some(item) => {
+                let Some(tables) = item.as_array_of_tables() else {
+                    return Err(root.invalid("drive", item, "an array of [[drive]] tables"));
+                };
+                for table in tables {
+                    let section = Section::new(path, &source, table);
+                    let systemd_name = match (
+                        section.str("systemd_name")?,
+                        section.str("mount_point")?,
+                        section.str("device")?,
+                    ) {
+                        (Some(name), _, _) => name,
+                        (None, None, None) => section.required_str("systemd_name")?,
+                        (None, mount_point, device) => systemd_name(mount_point, device)?,
+                    };
+                    let (systemd_name, suffixed) = UnitKind::split(&systemd_name);
+                    let kind = match section.str("unit_type")?.as_deref() {
+                        None => suffixed,
+                        Some("mount") => UnitKind::Mount,
+                        Some("swap") => UnitKind::Swap,
+                        Some(_) => {
+                            return Err(section.invalid(
+                                "unit_type",
+                                &table["unit_type"],
+                                "\"mount\" or \"swap\"",
+                            ))
+                        }
+                    };
+                    drives.push(DriveSpec {
+                        systemd_name,
+                        kind,
+                        display_name: section.str("display_name")?,
+                        auto_unmount_after: section.duration("auto_unmount_after")?,
+                        debounce: section.duration("debounce")?.unwrap_or(debounce),
+                        discovered: false,
+                        encrypted_device: section.str("encrypted_device")?,
+                        low_space: Some(section.low_space(low_space)?)
+                            .filter(|low_space| low_space.percent > 0),
+                        icon: section.str("icon")?,
+                        mount_options: section.str("mount_options")?,
+                    });
+                    section.deny_unknown()?;
+                }
+            }
+        }
+
+        if drives.is_empty() && !from_fstab {
+            return Err(ConfigError::NoDrives);
+        }
+
+        let mut config = Self::with_drives(drives);
+
+        if let Some(timeout) = root.duration("job_timeout")? {
+            // Every job would time out before systemd got to it
+            if timeout.is_zero() {
+                return Err(root.invalid("job_timeout", &doc["job_timeout"], "longer than 0s"));
+            }
+            config.job_timeout = timeout;
+        }
+
+        if let Some(ttl) = root.duration("auth_cache_ttl")? {
+            config.auth_cache_ttl = ttl;
+        }
+
+        if let Some(interval) = root.duration("poll_interval")? {
+            if interval.is_zero() {
+                return Err(root.invalid("poll_interval", &doc["poll_interval"], "longer than 0s"));
+            }
+            config.poll_interval = interval;
+        }
+
+        if let Some(interval) = root.duration("space_interval")? {
+            if interval.is_zero() {
+                return Err(root.invalid(
+                    "space_interval",
+                    &doc["space_interval"],
+                    "longer than 0s",
+                ));
+            }
+            config.space_interval = interval;
+        }
+
+        if let Some(instance) = root.str("instance")? {
+            config.instance = match instance.as_str() {
+                "system" => Instance::System,
+                "user" => Instance::User,
+                _ => {
+                    return Err(root.invalid(
+                        "instance",
+                        &doc["instance"],
+                        "\"system\" or \"user\"",
+                    ))
+                }
+            };
+        }
+
+        if let Some(action) = root.str("on_activate")? {
+            config.on_activate = match action.as_str() {
+                "menu" => OnActivate::Menu,
+                "toggle_mount" => OnActivate::ToggleMount,
+                "disconnect" => OnActivate::Disconnect,
+                _ => {
+                    return Err(root.invalid(
+                        "on_activate",
+                        &doc["on_activate"],
+                        "\"menu\", \"toggle_mount\" or \"disconnect\"",
+                    ))
+                }
+            };
+        }
+
+        if let Some(subject) = root.str("polkit_subject")? {
+            config.polkit_subject = match subject.as_str() {
+                "process" => PolkitSubject::Process,
+                "session" => PolkitSubject::Session,
+                "parent" => PolkitSubject::Parent,
+                _ => {
+                    return Err(root.invalid(
+                        "polkit_subject",
+                        &doc["polkit_subject"],
+                        "\"process\", \"session\" or \"parent\"",
+                    ))
+                }
+            };
+        }
+
+        if let Some(opener) = root.str("opener")? {
+            if opener.trim().is_empty() {
+                return Err(root.invalid("opener", &doc["opener"], "a command"));
+            }
+            config.opener = opener;
+        }
+
+        if let Some(tray) = root.bool("tray")? {
+            config.tray = tray;
+        }
+
+        if let Some(sync) = root.bool("sync_before_unmount")? {
+            config.sync_before_unmount = sync;
+        }
+
+        if let Some(confirm) = root.bool("confirm_disconnect")? {
+            config.confirm_disconnect = confirm;
+        }
+
+        if let Some(power_off) = root.bool("power_off_on_disconnect")? {
+            config.power_off_on_disconnect = power_off;
+        }
+
+        if let Some(retries) = root.count("mount_retries")? {
+            config.mount_retries = retries;
+        }
+
+        if let Some(allow) = root.bool("allow_without_polkit")? {
+            config.allow_without_polkit = allow;
+        }
+
+        if let Some(addr) = root.str("metrics_addr")? {
+            config.metrics_addr = Some(addr.parse().map_err(|_| {
+                root.invalid(
+                    "metrics_addr",
+                    &doc["metrics_addr"],
+                    "an address like \"127.0.0.1:9103\"",
+                )
+            })?);
+        }
+
+        if let Some(address) = root.str("bus_address")? {
+            config.bus_address = Some(address.parse().map_err(|_| {
+                root.invalid(
+                    "bus_address",
+                    &doc["bus_address"],
+                    "a D-Bus address like \"unix:path=/run/dbus/system_bus_socket\"",
+                )
+            })?);
+        }
+
+        if let Some(timeout) = root.count("notification_timeout_ms")? {
+            config.notification_timeout = Some(Duration::from_millis(timeout.into()));
+        }
+
+        if let Some(notify) = root.bool("notify_on_success")? {
+            config.notify_on_success = notify;
+        }
+
+        if let Some(icon) = root.str("notify_icon_success")? {
+            config.notify_icon_success = icon;
+        }
+        if let Some(icon) = root.str("notify_icon_error")? {
+            config.notify_icon_error = icon;
+        }
+        if let Some(sound) = root.bool("notify_sound")? {
+            config.notify_sound = sound;
+        }
+
+        if let Some(group) = root.bool("group_partitions")? {
+            config.group_partitions = group;
+        }
+
+        if let Some(mnemonics) = root.bool("menu_mnemonics")? {
+            config.menu_mnemonics = mnemonics;
+        }
+
+        if let Some(show) = root.bool("show_automount_item")? {
+            config.menu.show_automount_item = show;
+        }
+        if let Some(show) = root.bool("show_state_items")? {
+            config.menu.show_state_items = show;
+        }
+        if let Some(show) = root.bool("show_path_item")? {
+            config.menu.show_path_item = show;
+        }
+        if let Some(show) = root.bool("show_about_item")? {
+            config.menu.show_about_item = show;
+        }
+        if let Some(order) = root.strings("menu_order")? {
+            if order
+                .iter()
+                .any(|name| !MENU_ACTIONS.contains(&name.as_str()))
+            {
+                return Err(root.invalid(
+                    "menu_order",
+                    &doc["menu_order"],
+                    "a list of menu actions, e.g. [\"mount\", \"disconnect\"]",
+                ));
+            }
+            config.menu.order = order;
+        }
+
+        if let Some(inhibit) = root.bool("inhibit_sleep")? {
+            config.inhibit_sleep = inhibit;
+        }
+
+        if let Some(verify) = root.bool("verify_automount")? {
+            config.verify_automount = verify;
+        }
+
+        if let Some(enabled) = root.bool("hooks")? {
+            config.hooks.enabled = enabled;
+        }
+        config.hooks.on_mounted = root.str("on_mounted")?;
+        config.hooks.on_unmounted = root.str("on_unmounted")?;
+        config.hooks.on_failed = root.str("on_failed")?;
+        if let Some(timeout) = root.duration("hook_timeout")? {
+            config.hooks.timeout = timeout;
+        }
+
+        if let Some(icons) = root.table("icons")? {
+            let theme = &mut config.icons;
+            for (key, icon) in [
+                ("mounted", &mut theme.mounted),
+                ("armed", &mut theme.armed),
+                ("unmounted", &mut theme.unmounted),
+                ("busy", &mut theme.busy),
+                ("failed", &mut theme.failed),
+            ] {
+                if let Some(name) = icons.str(key)? {
+                    *icon = name;
+                    theme.configured.push(key);
+                }
+            }
+            icons.deny_unknown()?;
+        }
+
+        root.deny_unknown()?;
+        Ok(config)
+    }
+}
+
+/// The unit name prefix of a drive given by where it's mounted, or by the
+/// device fstab mounts there.
+fn systemd_name(
+    mount_point: Option<String>,
+    device: Option<String>,
+) -> Result<String, ConfigError> {
+    match (mount_point, device) {
+        (Some(mount_point), _) => Ok(unit_name::from_mount_point(&mount_point)),
+        (None, Some(device)) => match unit_name::from_device(&device) {
+            Ok(Some(name)) => Ok(name),
+            Ok(None) => Err(ConfigError::UnknownDevice(device)),
+            Err(e) => Err(ConfigError::Io("/etc/fstab".into(), e)),
+        },
+        (None, None) => unreachable!("checked by the callers"),
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    default_paths().into_iter().next()
+}
+
+/// `$XDG_CONFIG_HOME/diski/config.toml`, then `~/.config/diski/config.toml`.
+fn default_paths() -> Vec<PathBuf> {
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+    let home = env::var_os("HOME").map(|home| Path::new(&home).join(".config"));
+
+    let mut paths: Vec<PathBuf> = [xdg, home]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join("diski").join("config.toml"))
+        .collect();
+    paths.dedup();
+    paths
+}
+
+/// `$XDG_CONFIG_HOME`, or `~/.config` when it isn't set.
+pub fn config_home() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+}
+
+/// Accepts a bare number of seconds or one suffixed with `ms`, `s`, `m` or `h`.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+    let value: u64 = value.parse().ok()?;
+
+    match unit.trim() {
+        "ms" => Some(Duration::from_millis(value)),
+        "" | "s" => Some(Duration::from_secs(value)),
+        "m" => value.checked_mul(60).map(Duration::from_secs),
+        "h" => value.checked_mul(60 * 60).map(Duration::from_secs),
+        _ => None,
+    }
+}
+
+struct Section<'a> {
+    path: &'a Path,
+    source: &'a str,
+    table: &'a Table,
+    /// Every key asked for so far, the rest are unknown, see [`Section::deny_unknown`].
+    read: RefCell<HashSet<String>>,
+}
+
+impl<'a> Section<'a> {
+    fn new(path: &'a Path, source: &'a str, table: &'a Table) -> Self {
+        Self {
+            path,
+            source,
+            table,
+            read: RefCell::default(),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Item> {
+        self.read.borrow_mut().insert(key.to_owned());
+        self.table.get(key)
+    }
+
+    /// Fails on the first key nothing asked for, a misspelled one would be
+    /// ignored otherwise. Called once the whole table has been read.
+    fn deny_unknown(&self) -> Result<(), ConfigError> {
+        let read = self.read.borrow();
+        match self.table.iter().find(|(key, _)| !read.contains(*key)) {
+            None => Ok(()),
+            Some((key, item)) => {
+                let span = self
+                    .table
+                    .get_key_value(key)
+                    .and_then(|(key, _)| key.span())
+                    .or_else(|| item.span());
+                Err(ConfigError::UnknownKey {
+                    path: self.path.to_owned(),
+                    key: key.to_owned(),
+                    line: self.line(span),
+                })
+            }
+        }
+    }
+
+    fn str(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_str()
+                .map(|s| Some(s.to_owned()))
+                .ok_or_else(|| self.invalid(key, item, "a string")),
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, ConfigError> {
+        let Some(item) = self.get(key) else {
+            return Ok(None);
+        };
+        item.as_array()
+            .and_then(|array| {
+                array
+                    .iter()
+                    .map(|value| value.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .map(Some)
+            .ok_or_else(|| self.invalid(key, item, "a list of strings"))
+    }
+
+    fn bool(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_bool()
+                .map(Some)
+                .ok_or_else(|| self.invalid(key, item, "true or false")),
+        }
+    }
+
+    fn count(&self, key: &str) -> Result<Option<u32>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_integer()
+                .and_then(|n| u32::try_from(n).ok())
+                .map(Some)
+                .ok_or_else(|| self.invalid(key, item, "a whole number of at least 0")),
+        }
+    }
+
+    /// `low_space_percent` and `low_space_interval` over `defaults`, a percentage of 0 turns the warning off.
+    fn low_space(&self, defaults: LowSpace) -> Result<LowSpace, ConfigError> {
+        let percent = match self.count("low_space_percent")? {
+            Some(percent) if percent > 100 => {
+                let item = &self.table["low_space_percent"];
+                return Err(self.invalid("low_space_percent", item, "a percentage from 0 to 100"));
+            }
+            Some(percent) => percent,
+            None => defaults.percent,
+        };
+        let interval = self
+            .duration("low_space_interval")?
+            .unwrap_or(defaults.interval);
+        if interval.is_zero() {
+            let item = &self.table["low_space_interval"];
+            return Err(self.invalid("low_space_interval", item, "longer than 0s"));
+        }
+
+        Ok(LowSpace { percent, interval })
+    }
+
+    fn table(&self, key: &str) -> Result<Option<Section<'a>>, ConfigError> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_table()
+                .map(|table| Some(Section::new(self.path, self.source, table)))
+                .ok_or_else(|| self.invalid(key, item, "a table")),
+        }
+    }
+
+    fn duration(&self, key: &str) -> Result<Option<Duration>, ConfigError> {
+        let Some(item) = self.get(key) else {
+            return Ok(None);
+        };
+
+        let duration = match (item.as_integer(), item.as_str()) {
+            (Some(secs), _) => u64::try_from(secs).ok().map(Duration::from_secs),
+            (_, Some(s)) => parse_duration(s),
+            _ => None,
+        };
+
+        duration.map(Some).ok_or_else(|| {
+            self.invalid(key, item, "a number of seconds or a duration like \"90s\"")
+        })
+    }
+
+    fn required_str(&self, key: &'static str) -> Result<String, ConfigError> {
+        self.str(key)?.ok_or_else(|| ConfigError::MissingKey {
+            path: self.path.to_owned(),
+            key,
+            line: self.line(self.table.span()),
+        })
+    }
+
+    fn invalid(&self, key: &str, item: &Item, expected: &'static str) -> ConfigError {
+        let span = self
+            .table
+            .get_key_value(key)
+            .and_then(|(key, _)| key.span())
+            .or_else(|| item.span());
+
+        ConfigError::InvalidKey {
+            path: self.path.to_owned(),
+            key: key.to_owned(),
+            line: self.line(span),
+            expected,
+        }
+    }
+
+    fn line(&self, span: Option<std::ops::Range<usize>>) -> usize {
+        span.map_or(0, |span| {
+            self.source[..span.start].matches('\n').count() + 1
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Syntax(PathBuf, toml_edit::TomlError),
+    InvalidKey {
+        path: PathBuf,
+        key: String,
+        line: usize,
+        expected: &'static str,
+    },
+    UnknownKey {
+        path: PathBuf,
+        key: String,
+        line: usize,
+    },
+    MissingKey {
+        path: PathBuf,
+        key: &'static str,
+        line: usize,
+    },
+    InvalidEnv(&'static str),
+    InvalidAddress(String, zbus::Error),
+    UnknownDevice(String),
+    NoDrives,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, e) => write!(f, "could not read {}: {e}", path.display()),
+            Self::Syntax(path, e) => write!(f, "{}: {e}", path.display()),
+            Self::InvalidKey {
+                path,
+                key,
+                line,
+                expected,
+            } => write!(
+                f,
+                "{}:{line}: `{key}` should be {expected}",
+                path.display()
+            ),
+            Self::UnknownKey { path, key, line } => {
+                write!(f, "{}:{line}: unknown key `{key}`", path.display())
+            }
+            Self::MissingKey { path, key, line } => write!(
+                f,
+                "{}:{line}: drive entry is missing `{key}`",
+                path.display()
+            ),
+            Self::InvalidEnv(var) => write!(
+                f,
+                "${var} should be a number of seconds or a duration like \"90s\""
+            ),
+            Self::InvalidAddress(source, e) => {
+                write!(f, "`{source}` is not a valid D-Bus address: {e}")
+            }
+            Self::UnknownDevice(device) => write!(f, "{device} has no entry in /etc/fstab"),
+            Self::NoDrives => write!(
+                f,
+                "no drives configured; add a [[drive]] entry to {} or run `{} [--user] [--no-tray] <systemd name | --mount <path> | --device <path>> [display name]`",
+                default_path().map_or("the config file".into(), |p| p.display().to_string()),
+                env!("CARGO_PKG_NAME"),
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(_, e) => Some(e),
+            Self::Syntax(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse_with_their_unit() {
+        assert_eq!(parse_duration("250ms"), Some(Duration::from_millis(250)));
+        assert_eq!(parse_duration("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Some(Duration::from_secs(300)));
+        assert_eq!(parse_duration("2h"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("5 parsecs"), None);
+    }
+
+    #[test]
+    fn overflowing_durations_are_rejected() {
+        assert_eq!(parse_duration(&format!("{}m", u64::MAX)), None);
+        assert_eq!(parse_duration(&format!("{}h", u64::MAX / 60)), None);
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+    }
+
+    #[test]
+    fn zero_job_timeout_is_rejected() {
+        let parse = |root: &str| {
+            let source = format!("{root}[[drive]]\nsystemd_name = \"mnt-backup\"\n");
+            Config::parse(Path::new("config.toml"), source, false)
+        };
+        let zero = parse("job_timeout = 0\n");
+        assert!(
+            matches!(&zero, Err(ConfigError::InvalidKey { key, .. }) if key == "job_timeout"),
+            "{zero:?}"
+        );
+        assert!(parse("job_timeout = \"0s\"\n").is_err());
+        assert!(parse(&format!("job_timeout = \"{}h\"\n", u64::MAX)).is_err());
+        assert_eq!(
+            parse("job_timeout = \"2m\"\n").unwrap().job_timeout,
+            Duration::from_secs(120)
+        );
+    }
+
+    #[test]
+    fn a_drive_icon_leaves_the_configured_states_alone() {
+        let source = "[icons]\nbusy = \"view-refresh\"\n[[drive]]\nsystemd_name = \"mnt-backup\"\n";
+        let mut icons = Config::parse(Path::new("config.toml"), source.into(), false)
+            .unwrap()
+            .icons;
+        icons.apply("backup-drive");
+
+        assert_eq!(icons.busy, "view-refresh");
+        for state in [
+            &icons.mounted,
+            &icons.armed,
+            &icons.unmounted,
+            &icons.failed,
+        ] {
+            assert_eq!(state, "backup-drive");
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_line() {
+        let unknown =
+            |source: &str| match Config::parse(Path::new("config.toml"), source.into(), false) {
+                Err(ConfigError::UnknownKey { key, line, .. }) => Some((key, line)),
+                Err(e) => panic!("{e}"),
+                Ok(_) => None,
+            };
+        let drive = "[[drive]]\nsystemd_name = \"mnt-backup\"\n";
+
+        assert_eq!(
+            unknown(&format!("{drive}dispaly_name = \"Backup\"\n")),
+            Some(("dispaly_name".into(), 3))
+        );
+        assert_eq!(
+            unknown(&format!("job_timout = 5\n{drive}")),
+            Some(("job_timout".into(), 1))
+        );
+        assert_eq!(
+            unknown(&format!("[icons]\nmounted = \"a\"\nbsy = \"b\"\n{drive}")),
+            Some(("bsy".into(), 3))
+        );
+        assert_eq!(
+            unknown(&format!(
+                "{drive}mount_point = \"/mnt/backup\"\ndisplay_name = \"Backup\"\n"
+            )),
+            None
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! Control socket at `$XDG_RUNTIME_DIR/diski.sock` for scripting diski.
+//!
+//! Clients write one command per line and get one reply per command:
+//!
+//! ```text
+//! mount <name>             start the .mount unit
+//! unmount <name>           stop the .mount unit, automount stays active
+//! remount <name>           stop and start the .mount unit
+//! read-only <name>         remount the filesystem read-only in place
+//! read-write <name>        remount the filesystem read-write in place
+//! disconnect <name>        stop both units so the drive can be unplugged
+//! enable-automount <name>  start the .automount unit
+//! disable-automount <name> stop the .automount unit, a current mount stays
+//! reset-failed <name>      clear the failed state of both units
+//! refresh <name>           read the unit states again
+//! mount-all                mount every drive that isn't mounted
+//! disconnect-all           disconnect every mounted drive
+//! status [<name>]          one `<name> mount=<state> automount=<state> [where=<path>]`
+//!                          line per drive, all of them when no name is given
+//! ```
+//!
+//! `<name>` is the drive's systemd name. Replies end with `ok`, or are a single
+//! `err <reason>` line. Requests are only queued when `ok` is sent, their outcome
+//! is reported through the usual notification.
+
+use std::{env, io, path::PathBuf, sync::Arc};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc, watch},
+};
+use tracing::warn;
+
+use crate::{
+    state::{AutomountState, MountState},
+    ClientRequests,
+};
+
+/// What `status` reports for a drive.
+#[derive(Debug, Clone)]
+pub struct Status {
+    pub mount: MountState,
+    pub automount: AutomountState,
+    pub path: Option<String>,
+}
+
+/// Removes the socket file once diski stops listening.
+pub struct Socket {
+    path: PathBuf,
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("diski.sock"))
+}
+
+/// Accepts connections in the background, requests are sent on the same
+/// channel the tray uses.
+pub fn listen(
+    names: Vec<String>,
+    status: watch::Receiver<Vec<Status>>,
+    requests: mpsc::UnboundedSender<(usize, ClientRequests)>,
+) -> io::Result<Socket> {
+    let path = default_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "$XDG_RUNTIME_DIR is not set"))?;
+
+    // A previous instance that didn't shut down cleanly leaves its socket behind,
+    // a live one still accepts and makes the bind below fail instead
+    if std::os::unix::net::UnixStream::connect(&path).is_err() {
+        let _ = std::fs::remove_file(&path);
+    }
+    let listener = UnixListener::bind(&path)?;
+
+    let names: Arc<[String]> = names.into();
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(
+                        stream,
+                        names.clone(),
+                        status.clone(),
+                        requests.clone(),
+                    ));
+                }
+                Err(e) => {
+                    warn!("Control socket stopped accepting connections: {e}");
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(Socket { path })
+}
+
+enum Command {
+    Request(usize, ClientRequests),
+    Status(Option<usize>),
+}
+
+async fn serve(
+    stream: UnixStream,
+    names: Arc<[String]>,
+    status: watch::Receiver<Vec<Status>>,
+    requests: mpsc::UnboundedSender<(usize, ClientRequests)>,
+) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = match parse(&line, &names) {
+            Ok(Command::Request(drive, request)) => match requests.send((drive, request)) {
+                Ok(()) => "ok\n".to_owned(),
+                Err(_) => "err shutting down\n".to_owned(),
+            },
+            Ok(Command::Status(drive)) => {
+                let status = status.borrow();
+                let mut reply = String::new();
+                for (index, (name, status)) in names.iter().zip(status.iter()).enumerate() {
+                    if drive.is_none_or(|drive| drive == index) {
+                        reply += &status_line(name, status);
+                    }
+                }
+                reply + "ok\n"
+            }
+            Err(reason) => format!("err {reason}\n"),
+        };
+
+        if write.write_all(reply.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+fn status_line(name: &str, status: &Status) -> String {
+    let mut line = format!(
+        "{name} mount={} automount={}",
+        status.mount.as_str(),
+        status.automount.as_str()
+    );
+    if let Some(path) = &status.path {
+        line += &format!(" where={path}");
+    }
+    line + "\n"
+}
+
+fn parse(line: &str, names: &[String]) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+
+    let request = match words.next() {
+        Some("mount") => ClientRequests::Mount,
+        Some("unmount") => ClientRequests::Unmount,
+        Some("remount") => ClientRequests::Remount,
+        Some("read-only") => ClientRequests::SetReadOnly(true),
+        Some("read-write") => ClientRequests::SetReadOnly(false),
+        Some("disconnect") => ClientRequests::PrepareDisconnect,
+        Some("enable-automount") => ClientRequests::EnableAutomounting,
+        Some("disable-automount") => ClientRequests::DisableAutomounting,
+        Some("reset-failed") => ClientRequests::ResetFailed,
+        Some("refresh") => ClientRequests::Refresh,
+        Some(command @ ("mount-all" | "disconnect-all")) => {
+            let request = match command {
+                "mount-all" => ClientRequests::MountAll,
+                _ => ClientRequests::DisconnectAll,
+            };
+            return match words.next() {
+                Some(_) => Err("too many arguments".into()),
+                None => {
+                    let (drive, request) = request.for_every_drive();
+                    Ok(Command::Request(drive, request))
+                }
+            };
+        }
+        Some("status") => {
+            let drive = words.next().map(|name| find(names, name)).transpose()?;
+            return match words.next() {
+                Some(_) => Err("too many arguments".into()),
+                None => Ok(Command::Status(drive)),
+            };
+        }
+        Some(command) => return Err(format!("unknown command `{command}`")),
+        None => return Err("empty command".into()),
+    };
+
+    let name = words.next().ok_or("missing drive name")?;
+    if words.next().is_some() {
+        return Err("too many arguments".into());
+    }
+
+    Ok(Command::Request(find(names, name)?, request))
+}
+
+fn find(names: &[String], name: &str) -> Result<usize, String> {
+    names
+        .iter()
+        .position(|drive| drive == name)
+        .ok_or_else(|| format!("unknown drive `{name}`"))
+}
@@ -0,0 +1,151 @@
+//! `org.diski.Drive1` objects on the session bus, one per drive at
+//! `/org/diski/Drive/<systemd name>` (anything but ASCII letters and digits
+//! replaced by `_`), under the well-known name `org.diski`. Requests for
+//! every drive at once go to the `org.diski.Manager1` object at `/org/diski`.
+//!
+//! Like the control socket, methods only queue a request and return, the
+//! outcome is reported through the usual notification.
+
+use tokio::sync::{mpsc, watch};
+use tracing::warn;
+use zbus::{fdo, interface, object_server::SignalEmitter};
+
+use crate::{control::Status, unit_name, ClientRequests};
+
+const NAME: &str = "org.diski";
+
+struct Drive {
+    index: usize,
+    status: Status,
+    requests: mpsc::UnboundedSender<(usize, ClientRequests)>,
+}
+
+impl Drive {
+    fn send(&self, request: ClientRequests) -> fdo::Result<()> {
+        self.requests
+            .send((self.index, request))
+            .map_err(|_| fdo::Error::Failed("diski is shutting down".into()))
+    }
+}
+
+#[interface(name = "org.diski.Drive1")]
+impl Drive {
+    fn mount(&self) -> fdo::Result<()> {
+        self.send(ClientRequests::Mount)
+    }
+
+    fn unmount(&self) -> fdo::Result<()> {
+        self.send(ClientRequests::Unmount)
+    }
+
+    fn disconnect(&self) -> fdo::Result<()> {
+        self.send(ClientRequests::PrepareDisconnect)
+    }
+
+    /// The mount unit's raw sub-state, like `mounted` or `dead`.
+    #[zbus(property)]
+    fn mount_state(&self) -> &str {
+        self.status.mount.as_str()
+    }
+
+    #[zbus(property)]
+    fn automount_state(&self) -> &str {
+        self.status.automount.as_str()
+    }
+
+    /// Empty while the mount point is unknown.
+    #[zbus(property, name = "Where")]
+    fn path(&self) -> &str {
+        self.status.path.as_deref().unwrap_or_default()
+    }
+}
+
+struct Manager {
+    requests: mpsc::UnboundedSender<(usize, ClientRequests)>,
+}
+
+impl Manager {
+    fn send(&self, request: ClientRequests) -> fdo::Result<()> {
+        self.requests
+            .send(request.for_every_drive())
+            .map_err(|_| fdo::Error::Failed("diski is shutting down".into()))
+    }
+}
+
+#[interface(name = "org.diski.Manager1")]
+impl Manager {
+    /// Mounts every drive that isn't mounted.
+    fn mount_all(&self) -> fdo::Result<()> {
+        self.send(ClientRequests::MountAll)
+    }
+
+    /// Disconnects every mounted drive.
+    fn disconnect_all(&self) -> fdo::Result<()> {
+        self.send(ClientRequests::DisconnectAll)
+    }
+}
+
+fn object_path(systemd_name: &str) -> String {
+    format!("/org/diski/Drive/{}", unit_name::identifier(systemd_name))
+}
+
+/// Exports the drives and keeps their properties in step with `status`,
+/// requests are sent on the same channel the tray uses.
+pub async fn serve(
+    names: Vec<String>,
+    mut status: watch::Receiver<Vec<Status>>,
+    requests: mpsc::UnboundedSender<(usize, ClientRequests)>,
+) -> zbus::Result<zbus::Connection> {
+    let initial = status.borrow_and_update().clone();
+    let paths: Vec<String> = names.iter().map(|name| object_path(name)).collect();
+
+    let manager = Manager {
+        requests: requests.clone(),
+    };
+    let mut builder = zbus::connection::Builder::session()?
+        .name(NAME)?
+        .serve_at("/org/diski", manager)?;
+    for (index, (path, status)) in paths.iter().zip(initial).enumerate() {
+        let drive = Drive {
+            index,
+            status,
+            requests: requests.clone(),
+        };
+        builder = builder.serve_at(path.as_str(), drive)?;
+    }
+    let conn = builder.build().await?;
+
+    let server = conn.object_server().clone();
+    tokio::spawn(async move {
+        while status.changed().await.is_ok() {
+            let statuses = status.borrow_and_update().clone();
+            for (path, new) in paths.iter().zip(statuses) {
+                let Ok(drive) = server.interface::<_, Drive>(path.as_str()).await else {
+                    continue;
+                };
+                let mut object = drive.get_mut().await;
+                if let Err(e) = update(&mut object, new, drive.signal_emitter()).await {
+                    warn!("Could not announce the state of {path}: {e}");
+                }
+            }
+        }
+    });
+
+    Ok(conn)
+}
+
+async fn update(drive: &mut Drive, new: Status, emitter: &SignalEmitter<'_>) -> zbus::Result<()> {
+    let old = std::mem::replace(&mut drive.status, new);
+
+    if old.mount != drive.status.mount {
+        drive.mount_state_changed(emitter).await?;
+    }
+    if old.automount != drive.status.automount {
+        drive.automount_state_changed(emitter).await?;
+    }
+    if old.path != drive.status.path {
+        drive.where_changed(emitter).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use std::{env, fmt::Write as _};
+
+use zbus_systemd::systemd1::{MountProxy, SwapProxy, UnitProxy};
+
+use crate::{
+    auth,
+    config::{Instance, UnitKind, DEFAULT_POLL_INTERVAL},
+    exit_code::Failure,
+    udisks::{self, Identity},
+    Bus,
+};
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Use the user's systemd instance instead of the system one
+    #[arg(long, visible_alias = "session")]
+    pub user: bool,
+    /// Connect to this D-Bus address instead of the instance's
+    #[arg(long, value_name = "address")]
+    pub bus_address: Option<zbus::Address>,
+    /// Leave the drive's serial number out
+    #[arg(long)]
+    pub redact_serials: bool,
+    #[arg(value_name = "systemd name")]
+    pub name: String,
+}
+
+/// `diski diagnose`: prints what diski sees of the drive and its bus, to be
+/// pasted into bug reports. Units that don't exist are reported, not failed on.
+pub async fn run(args: Args) -> Result<(), Failure> {
+    let Args {
+        user,
+        bus_address: address,
+        redact_serials,
+        name,
+    } = args;
+    let instance = Instance::from_user(user);
+
+    let bus_label = match &address {
+        Some(address) => address.to_string(),
+        None => {
+            let var = match instance {
+                Instance::System => "DBUS_SYSTEM_BUS_ADDRESS",
+                Instance::User => "DBUS_SESSION_BUS_ADDRESS",
+            };
+            env::var(var).unwrap_or_else(|_| "the default address".into())
+        }
+    };
+    let bus = Bus::connect(instance, address, DEFAULT_POLL_INTERVAL)
+        .await
+        .map_err(|e| format!("could not connect to the {instance} bus at {bus_label}: {e}"))?;
+
+    let mut report = String::new();
+    let _ = writeln!(
+        report,
+        "{} {}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    let _ = writeln!(report, "bus: {instance} at {bus_label}");
+    let _ = writeln!(
+        report,
+        "connection: {}",
+        bus.conn
+            .unique_name()
+            .map_or("(no unique name)".into(), |name| name.to_string())
+    );
+    let _ = writeln!(report, "systemd: {}", show(&bus.manager.version().await));
+    let _ = writeln!(
+        report,
+        "signals: {}",
+        match bus.polling {
+            None => "subscribed".into(),
+            Some(interval) => format!("refused, polling every {}s", interval.as_secs_f32()),
+        }
+    );
+    // polkit is only asked on the system bus, but reachable is worth knowing either way
+    let polkit = match auth::polkit_available(&bus.system).await {
+        true => "reachable",
+        false => "not running or activatable on the system bus",
+    };
+    let _ = writeln!(report, "polkit: {polkit}");
+
+    let (name, kind) = UnitKind::split(&name);
+    let mut units = vec![format!("{name}.{}", kind.suffix())];
+    if kind == UnitKind::Mount {
+        units.push(format!("{name}.automount"));
+    }
+    let mut what = None;
+    for unit in units {
+        let _ = writeln!(report, "\n{unit}");
+        let properties = match properties(&bus, &unit).await {
+            Ok(properties) => properties,
+            Err(e) => {
+                let _ = writeln!(report, "  (could not be loaded: {e})");
+                continue;
+            }
+        };
+        for (key, value) in &properties {
+            let _ = writeln!(report, "  {key}: {}", show(value));
+        }
+        what = what.or_else(|| {
+            properties
+                .into_iter()
+                .find(|(key, _)| *key == "What")
+                .and_then(|(_, value)| value.ok())
+                .filter(|what| !what.is_empty())
+        });
+    }
+
+    if let Some(what) = what {
+        let identity = Identity::lookup(&bus.system, &what).await;
+        let serial = match udisks::serial_of(&bus.system, &what).await {
+            Some(_) if redact_serials => "(redacted)".to_owned(),
+            Some(serial) => serial,
+            None => "(unknown)".to_owned(),
+        };
+        let drive = udisks::drive_of(&bus.system, &what)
+            .await
+            .map(|(_, name)| name);
+        let _ = writeln!(report, "\ndevice");
+        for (key, value) in [
+            ("label", identity.label),
+            ("uuid", identity.uuid),
+            ("device", identity.device),
+            ("drive", drive),
+            ("serial", Some(serial)),
+        ] {
+            let _ = writeln!(
+                report,
+                "  {key}: {}",
+                value.as_deref().unwrap_or("(unknown)")
+            );
+        }
+    }
+
+    print!("{report}");
+    Ok(())
+}
+
+/// Everything the report shows about one unit, read once up front. `load_unit`
+/// still hands out an object for units systemd has no file for, reported as `not-found`.
+async fn properties(
+    bus: &Bus,
+    unit: &str,
+) -> zbus::Result<Vec<(&'static str, zbus::Result<String>)>> {
+    let path = bus.manager.load_unit(unit.to_owned()).await?;
+    let proxy = UnitProxy::new(&bus.conn, path.clone()).await?;
+    let mut properties = vec![
+        ("LoadState", proxy.load_state().await),
+        ("ActiveState", proxy.active_state().await),
+        ("SubState", proxy.sub_state().await),
+        ("UnitFileState", proxy.unit_file_state().await),
+        ("FragmentPath", proxy.fragment_path().await),
+    ];
+
+    if unit.ends_with(".mount") {
+        let mount = MountProxy::new(&bus.conn, path).await?;
+        properties.extend([
+            ("Where", mount.where_property().await),
+            ("What", mount.what().await),
+            ("Type", mount.type_property().await),
+            ("Options", mount.options().await),
+            ("Result", mount.result().await),
+        ]);
+    } else if unit.ends_with(".swap") {
+        let swap = SwapProxy::new(&bus.conn, path).await?;
+        properties.extend([
+            ("What", swap.what().await),
+            ("Options", swap.options().await),
+            ("Result", swap.result().await),
+        ]);
+    }
+
+    Ok(properties)
+}
+
+fn show(value: &zbus::Result<String>) -> String {
+    match value {
+        Ok(value) if value.is_empty() => "(empty)".into(),
+        Ok(value) => value.clone(),
+        Err(e) => format!("(unavailable: {e})"),
+    }
+}
@@ -0,0 +1,84 @@
+//! Exit statuses of the one-shot subcommands, `status`, `mount` and `watch`,
+//! so scripts can tell failures apart without parsing the message:
+//!
+//! ```text
+//! 0  success
+//! 1  any other failure, e.g. a bad argument or no bus
+//! 2  the drive's units don't exist
+//! 3  systemd refused the request, polkit didn't authorize it
+//! 4  waiting for the drive ran out of time
+//! ```
+//!
+//! The tray exits with 2 as well once none of its configured drives have
+//! units, drives missing theirs are skipped while any other is left.
+
+use std::fmt;
+
+use crate::JobError;
+
+pub const FAILED: i32 = 1;
+pub const UNIT_NOT_FOUND: i32 = 2;
+pub const UNAUTHORIZED: i32 = 3;
+pub const TIMED_OUT: i32 = 4;
+
+#[derive(Debug)]
+pub enum Failure {
+    Failed(Box<dyn std::error::Error + Send + Sync>),
+    UnitNotFound(String),
+    Unauthorized(String),
+    TimedOut(String),
+}
+
+impl Failure {
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Failed(_) => FAILED,
+            Self::UnitNotFound(_) => UNIT_NOT_FOUND,
+            Self::Unauthorized(_) => UNAUTHORIZED,
+            Self::TimedOut(_) => TIMED_OUT,
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Failed(e) => write!(f, "{e}"),
+            Self::UnitNotFound(message) | Self::Unauthorized(message) | Self::TimedOut(message) => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+/// Sorts the errors bubbling up from systemd by what they mean for the caller.
+impl<E: Into<Box<dyn std::error::Error + Send + Sync>>> From<E> for Failure {
+    fn from(e: E) -> Self {
+        let e = e.into();
+        if let Some(JobError::TimedOut(_)) = e.downcast_ref::<JobError>() {
+            return Self::TimedOut(e.to_string());
+        }
+        if let Some(zbus::Error::MethodError(name, _, _)) = e.downcast_ref::<zbus::Error>() {
+            match name.as_str() {
+                "org.freedesktop.DBus.Error.AccessDenied"
+                | "org.freedesktop.DBus.Error.InteractiveAuthorizationRequired" => {
+                    return Self::Unauthorized(e.to_string());
+                }
+                "org.freedesktop.systemd1.NoSuchUnit" => return Self::UnitNotFound(e.to_string()),
+                _ => {}
+            }
+        }
+        Self::Failed(e)
+    }
+}
+
+/// Fails with [`UNIT_NOT_FOUND`] unless the drive's units exist.
+pub fn found(missing: Vec<String>) -> Result<(), Failure> {
+    match missing.as_slice() {
+        [] => Ok(()),
+        units => Err(Failure::UnitNotFound(format!(
+            "no systemd unit {} found",
+            units.join(" or ")
+        ))),
+    }
+}
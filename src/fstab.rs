@@ -0,0 +1,94 @@
+use std::{fs, io};
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub source: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub options: Vec<String>,
+}
+
+impl Entry {
+    pub fn has_option(&self, option: &str) -> bool {
+        self.options.iter().any(|o| o == option)
+    }
+
+    /// Mounted on first access rather than at boot, the setup diski is made for.
+    pub fn is_on_demand(&self) -> bool {
+        self.has_option("x-systemd.automount")
+            || self.has_option("noauto") && (self.has_option("nofail") || self.has_option("user"))
+    }
+}
+
+/// `/etc/fstab` followed by the `*.fstab` files in `/etc/fstab.d`, in name order.
+pub fn read() -> io::Result<Vec<Entry>> {
+    let mut entries = parse(&fs::read_to_string("/etc/fstab")?);
+
+    let mut extra: Vec<_> = match fs::read_dir("/etc/fstab.d") {
+        Ok(dir) => dir
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "fstab"))
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    extra.sort();
+    for path in extra {
+        entries.extend(parse(&fs::read_to_string(path)?));
+    }
+
+    Ok(entries)
+}
+
+pub fn parse(fstab: &str) -> Vec<Entry> {
+    fstab
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            if line.starts_with('#') {
+                return None;
+            }
+
+            let mut fields = line.split_whitespace().map(unescape);
+            let (source, mount_point) = (fields.next()?, fields.next()?);
+            let fs_type = fields.next().unwrap_or_else(|| "auto".into());
+            let options = fields
+                .next()
+                .map(|options| options.split(',').map(str::to_owned).collect())
+                .unwrap_or_default();
+
+            Some(Entry {
+                source,
+                mount_point,
+                fs_type,
+                options,
+            })
+        })
+        .collect()
+}
+
+/// fstab spells whitespace in fields as `\040` style octal escapes.
+pub fn unescape(field: &str) -> String {
+    let mut out = Vec::with_capacity(field.len());
+    let mut bytes = field.as_bytes();
+
+    while let Some((&byte, rest)) = bytes.split_first() {
+        let escaped = rest
+            .get(..3)
+            .filter(|_| byte == b'\\')
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+
+        match escaped {
+            Some(value) => {
+                out.push(value);
+                bytes = &rest[3..];
+            }
+            None => {
+                out.push(byte);
+                bytes = rest;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,89 @@
+use std::{collections::BTreeSet, fs, path::Path, time::Duration};
+
+use tokio::{task, time};
+use tracing::debug;
+
+const SCAN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Names of the processes with files open, or their working directory, below `mount_point`.
+///
+/// Only processes we may inspect are seen, so without root this is mostly the user's own.
+/// `None` when the scan failed or gave up rather than hold up a disconnect, so
+/// it is unknown whether anything still uses the mount.
+pub async fn of(mount_point: &str) -> Option<Vec<String>> {
+    let mount_point = mount_point.to_owned();
+    let scan = task::spawn_blocking(move || scan(Path::new(&mount_point)));
+
+    match time::timeout(SCAN_TIMEOUT, scan).await {
+        Ok(Ok(holders)) => holders,
+        Ok(Err(e)) => {
+            debug!("Scanning for open files failed: {e}");
+            None
+        }
+        Err(_) => {
+            debug!("Scanning for open files took longer than {SCAN_TIMEOUT:?}");
+            None
+        }
+    }
+}
+
+fn scan(mount_point: &Path) -> Option<Vec<String>> {
+    let procs = match fs::read_dir("/proc") {
+        Ok(procs) => procs,
+        Err(e) => {
+            debug!("Could not list processes: {e}");
+            return None;
+        }
+    };
+
+    let mut holders = BTreeSet::new();
+    for entry in procs.flatten() {
+        let proc = entry.path();
+        if !entry
+            .file_name()
+            .to_string_lossy()
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+        {
+            continue;
+        }
+
+        let fds = fs::read_dir(proc.join("fd"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|fd| fd.path());
+        let holds = [proc.join("cwd")]
+            .into_iter()
+            .chain(fds)
+            .filter_map(|link| fs::read_link(link).ok())
+            .any(|target| target.starts_with(mount_point));
+
+        if holds {
+            if let Ok(name) = fs::read_to_string(proc.join("comm")) {
+                holders.insert(name.trim_end().to_owned());
+            }
+        }
+    }
+
+    Some(holders.into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_files_hold_their_directory() {
+        let dir = std::env::temp_dir().join(format!("diski-holders-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let idle = scan(&dir);
+        let file = fs::File::create(dir.join("open")).unwrap();
+        let held = scan(&dir);
+        drop(file);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(idle, Some(Vec::new()));
+        assert!(held.is_some_and(|holders| !holders.is_empty()));
+    }
+}
@@ -0,0 +1,63 @@
+//! User commands run on a drive's transitions, from `on_mounted`,
+//! `on_unmounted` and `on_failed` in the config file:
+//!
+//! ```toml
+//! on_mounted = "systemctl --user start backup@{name}.service"
+//! ```
+//!
+//! The command is split on whitespace and run without a shell, `{name}` and
+//! `{path}` in its words are replaced by the systemd name and mount point.
+//! Hooks run detached and are killed once they outlive `hook_timeout`.
+
+use std::{process::Stdio, time::Duration};
+
+use tokio::{process::Command, time};
+use tracing::{debug, info, warn};
+
+/// Starts the hook and returns right away, its outcome is only logged.
+pub fn run(template: &str, name: &str, path: Option<&str>, timeout: Duration, dry_run: bool) {
+    let words: Vec<String> = template
+        .split_whitespace()
+        .map(|word| {
+            word.replace("{name}", name)
+                .replace("{path}", path.unwrap_or_default())
+        })
+        .collect();
+    let Some((program, args)) = words.split_first() else {
+        return;
+    };
+    if dry_run {
+        info!(
+            "(dry-run) Would run the hook `{}` for {name}",
+            words.join(" ")
+        );
+        return;
+    }
+
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Could not run the hook {program} for {name}: {e}");
+            return;
+        }
+    };
+
+    let program = program.clone();
+    let name = name.to_owned();
+    tokio::spawn(async move {
+        match time::timeout(timeout, child.wait()).await {
+            Ok(Ok(status)) if status.success() => debug!("Hook {program} for {name} exited"),
+            Ok(Ok(status)) => warn!("Hook {program} for {name} exited with {status}"),
+            Ok(Err(e)) => warn!("Could not wait for the hook {program} for {name}: {e}"),
+            Err(_) => warn!(
+                "Hook {program} for {name} was killed after running for {}s",
+                timeout.as_secs()
+            ),
+        }
+    });
+}
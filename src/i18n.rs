@@ -0,0 +1,146 @@
+use std::{env, fmt, sync::OnceLock};
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use fluent_langneg::{negotiate_languages, NegotiationStrategy};
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+/// Every shipped translation, `en` doubles as the fallback for missing messages.
+const BUNDLED: &[(&str, &str)] = &[("en", include_str!("../i18n/en/diski.ftl"))];
+
+const FALLBACK: &str = "en";
+
+/// Formats a message from the catalogue, e.g. `tr!("menu-path", path = path)`.
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::message($id, &[])
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::message($id, &[$((stringify!($name), &$value as &dyn std::fmt::Display)),+])
+    };
+}
+pub(crate) use tr;
+
+pub fn message(id: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    let catalogue = CATALOGUE.get_or_init(Catalogue::load);
+
+    catalogue
+        .bundles
+        .iter()
+        .find_map(|bundle| format(bundle, id, args))
+        .unwrap_or_else(|| id.to_owned())
+}
+
+static CATALOGUE: OnceLock<Catalogue> = OnceLock::new();
+
+struct Catalogue {
+    /// The negotiated locales' in order of preference, the fallback last.
+    bundles: Vec<FluentBundle<FluentResource>>,
+}
+
+impl Catalogue {
+    fn load() -> Self {
+        let available: Vec<LanguageIdentifier> = BUNDLED
+            .iter()
+            .filter_map(|(lang, _)| lang.parse().ok())
+            .collect();
+        let fallback: LanguageIdentifier = FALLBACK.parse().expect("a valid language tag");
+
+        // `pt-BR` falls back to plain `pt` when only that is shipped
+        let requested: Vec<LanguageIdentifier> = requested_locale().into_iter().collect();
+        let negotiated = negotiate_languages(
+            &requested,
+            &available,
+            Some(&fallback),
+            NegotiationStrategy::Filtering,
+        );
+
+        let bundles = negotiated
+            .into_iter()
+            .filter_map(|lang| {
+                let (_, source) = BUNDLED
+                    .iter()
+                    .find(|(name, _)| name.parse().ok().as_ref() == Some(lang))?;
+                bundle(lang.clone(), source)
+            })
+            .collect();
+
+        Self { bundles }
+    }
+}
+
+fn bundle(lang: LanguageIdentifier, source: &str) -> Option<FluentBundle<FluentResource>> {
+    let resource = FluentResource::try_new(source.to_owned())
+        .map_err(|(_, errors)| warn!("The {lang} translation has errors: {errors:?}"))
+        .ok()?;
+
+    let mut bundle = FluentBundle::new_concurrent(vec![lang]);
+    // The isolation marks would end up in notifications and menus verbatim
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).ok()?;
+    Some(bundle)
+}
+
+/// Arguments that read as numbers are passed as numbers, so plurals select on them.
+fn format(
+    bundle: &FluentBundle<FluentResource>,
+    id: &str,
+    args: &[(&str, &dyn fmt::Display)],
+) -> Option<String> {
+    let pattern = bundle.get_message(id)?.value()?;
+
+    let values: Vec<String> = args.iter().map(|(_, value)| value.to_string()).collect();
+    let mut fluent_args = FluentArgs::new();
+    for ((name, _), value) in args.iter().zip(&values) {
+        fluent_args.set(*name, FluentValue::try_number(value));
+    }
+    let mut errors = Vec::new();
+    let message = bundle.format_pattern(pattern, Some(&fluent_args), &mut errors);
+    if !errors.is_empty() {
+        warn!("Could not format {id}: {errors:?}");
+    }
+    Some(message.into_owned())
+}
+
+/// The messages locale in POSIX precedence, stripped of codeset and modifier.
+fn requested_locale() -> Option<LanguageIdentifier> {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())?;
+
+    let locale = locale.split(['.', '@']).next().unwrap_or_default();
+    match locale {
+        "" | "C" | "POSIX" => None,
+        locale => locale.replace('_', "-").parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_translations_parse() {
+        for (lang, source) in BUNDLED {
+            let lang: LanguageIdentifier = lang.parse().unwrap();
+            assert!(bundle(lang.clone(), source).is_some(), "{lang}");
+        }
+    }
+
+    #[test]
+    fn plurals_select_on_numbers() {
+        let source = "\
+files = { $count ->
+    [one] one file
+   *[other] { $count } files
+}
+";
+        let bundle = bundle(FALLBACK.parse().unwrap(), source).unwrap();
+        let files = |count: &dyn fmt::Display| format(&bundle, "files", &[("count", count)]);
+        assert_eq!(files(&1).as_deref(), Some("one file"));
+        assert_eq!(files(&3).as_deref(), Some("3 files"));
+        assert_eq!(files(&"many").as_deref(), Some("many files"));
+        assert_eq!(format(&bundle, "missing", &[]), None);
+    }
+}
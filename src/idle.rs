@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use tokio::{
+    fs, select,
+    time::{self, Instant},
+};
+use tracing::debug;
+
+use crate::unit_name;
+
+const POLL: Duration = Duration::from_secs(15);
+
+/// Resolves once the device behind `what` has seen no I/O for `after`.
+///
+/// Activity is taken from the completed read and write counters in
+/// `/proc/diskstats`, which only exist for block devices.
+pub async fn wait(what: &str, after: Duration) -> Result<(), String> {
+    let device = device_name(what)
+        .await
+        .ok_or_else(|| format!("{what} is not a block device"))?;
+
+    let mut last = io_count(&device).await?;
+    let timer = time::sleep(after);
+    tokio::pin!(timer);
+    let mut poll = time::interval(POLL);
+
+    loop {
+        select! {
+            _ = &mut timer => return Ok(()),
+            _ = poll.tick() => {
+                let current = io_count(&device).await?;
+                if current != last {
+                    debug!("{device} is busy, postponing the idle unmount");
+                    last = current;
+                    timer.as_mut().reset(Instant::now() + after);
+                }
+            }
+        }
+    }
+}
+
+/// The kernel name, e.g. `sdb1`, of the device a mount source points at.
+async fn device_name(what: &str) -> Option<String> {
+    let path = unit_name::device_path(what);
+    // Network shares and the like have no block device to watch
+    if !path.starts_with("/dev/") {
+        return None;
+    }
+
+    let path = fs::canonicalize(path).await.ok()?;
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+async fn io_count(device: &str) -> Result<(u64, u64), String> {
+    let stats = fs::read_to_string("/proc/diskstats")
+        .await
+        .map_err(|e| format!("could not read /proc/diskstats: {e}"))?;
+
+    stats
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.get(2) == Some(&device))
+        .and_then(|fields| Some((fields.get(3)?.parse().ok()?, fields.get(7)?.parse().ok()?)))
+        .ok_or_else(|| format!("{device} is missing from /proc/diskstats"))
+}
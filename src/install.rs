@@ -0,0 +1,239 @@
+//! `diski install` and `diski uninstall`: start diski at login through a
+//! systemd user service, or an XDG autostart entry for sessions without one.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use tokio::process::Command;
+
+use crate::{config, ExResult};
+
+#[derive(Debug, clap::Args)]
+pub struct InstallArgs {
+    /// The drive's systemd name
+    #[arg(long, value_name = "systemd name")]
+    name: String,
+    /// The name the tray shows for the drive
+    #[arg(long = "display", value_name = "name")]
+    display_name: Option<String>,
+    /// Use the user's systemd instance instead of the system one
+    #[arg(long, visible_alias = "session")]
+    user: bool,
+    /// Write an XDG autostart entry instead of a systemd user service
+    #[arg(long)]
+    autostart: bool,
+    /// Enable and start the service right away
+    #[arg(long, conflicts_with = "autostart")]
+    enable: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct UninstallArgs {
+    /// The drive's systemd name
+    #[arg(long, value_name = "systemd name")]
+    name: String,
+}
+
+impl InstallArgs {
+    /// What diski itself is started with, the binary first.
+    fn command(&self) -> ExResult<Vec<String>> {
+        let exe =
+            env::current_exe().map_err(|e| format!("could not find the diski binary: {e}"))?;
+        let mut command = vec![exe.to_string_lossy().into_owned()];
+        if self.user {
+            command.push("--user".into());
+        }
+        command.push(self.name.clone());
+        command.extend(self.display_name.clone());
+        Ok(command)
+    }
+
+    fn title(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Writes the service (or, with `--autostart`, the desktop entry) and
+/// enables the service right away with `--enable`.
+pub async fn install(options: InstallArgs) -> ExResult<()> {
+    let command = options.command()?;
+
+    if options.autostart {
+        let path = desktop_path(&options.name)?;
+        write(&path, &desktop_entry(options.title(), &command))?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+
+    let path = service_path(&options.name)?;
+    write(&path, &service(options.title(), &command))?;
+    println!("Wrote {}", path.display());
+
+    systemctl(&["daemon-reload"]).await?;
+    if options.enable {
+        systemctl(&["enable", "--now", &service_name(&options.name)]).await?;
+    }
+
+    Ok(())
+}
+
+/// Disables the service if it was installed and removes whatever `install` wrote.
+pub async fn uninstall(options: UninstallArgs) -> ExResult<()> {
+    let mut removed = false;
+
+    let service = service_path(&options.name)?;
+    if service.exists() {
+        systemctl(&["disable", "--now", &service_name(&options.name)]).await?;
+        remove(&service)?;
+        systemctl(&["daemon-reload"]).await?;
+        removed = true;
+    }
+
+    let desktop = desktop_path(&options.name)?;
+    if desktop.exists() {
+        remove(&desktop)?;
+        removed = true;
+    }
+
+    if !removed {
+        return Err(format!("diski is not installed for {}", options.name).into());
+    }
+    Ok(())
+}
+
+fn service_name(name: &str) -> String {
+    format!("diski-{name}.service")
+}
+
+fn service_path(name: &str) -> ExResult<PathBuf> {
+    let home = config::config_home().ok_or("neither $XDG_CONFIG_HOME nor $HOME is set")?;
+    Ok(home.join("systemd").join("user").join(service_name(name)))
+}
+
+fn desktop_path(name: &str) -> ExResult<PathBuf> {
+    let home = config::config_home().ok_or("neither $XDG_CONFIG_HOME nor $HOME is set")?;
+    // Backslashes from systemd's escaping aren't welcome in desktop file IDs
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Ok(home.join("autostart").join(format!("diski-{id}.desktop")))
+}
+
+fn service(title: &str, command: &[String]) -> String {
+    let exec: Vec<String> = command.iter().map(|arg| quote_systemd(arg)).collect();
+    format!(
+        "[Unit]\n\
+         Description=diski tray icon for {}\n\
+         PartOf=graphical-session.target\n\
+         After=graphical-session.target\n\
+         \n\
+         [Service]\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=graphical-session.target\n",
+        title.replace('%', "%%").replace('\n', " "),
+        exec.join(" ")
+    )
+}
+
+fn desktop_entry(title: &str, command: &[String]) -> String {
+    let exec: Vec<String> = command.iter().map(|arg| quote_desktop(arg)).collect();
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=diski ({})\n\
+         Exec={}\n\
+         NoDisplay=true\n\
+         X-GNOME-Autostart-enabled=true\n",
+        escape_desktop(title),
+        escape_desktop(&exec.join(" "))
+    )
+}
+
+/// A double-quoted word for `ExecStart=`, with specifiers and variables kept literal.
+fn quote_systemd(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    for c in arg.chars() {
+        match c {
+            '\\' | '"' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '%' => out.push_str("%%"),
+            '$' => out.push_str("$$"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A double-quoted argument for `Exec=`, as the Desktop Entry spec quotes them.
+fn quote_desktop(arg: &str) -> String {
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '%' => out.push_str("%%"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The string escapes every desktop entry value goes through before `Exec=` is split.
+fn escape_desktop(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+}
+
+fn write(path: &Path, contents: &str) -> ExResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {e}", dir.display()))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("could not write {}: {e}", path.display()).into())
+}
+
+fn remove(path: &Path) -> ExResult<()> {
+    match fs::remove_file(path) {
+        Ok(()) => {
+            println!("Removed {}", path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("could not remove {}: {e}", path.display()).into()),
+    }
+}
+
+async fn systemctl(args: &[&str]) -> ExResult<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .await
+        .map_err(|e| format!("could not run systemctl: {e}"))?;
+    if !status.success() {
+        return Err(format!("systemctl --user {} failed with {status}", args.join(" ")).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,13 @@
+use tracing_subscriber::EnvFilter;
+
+/// Installs a stderr subscriber filtered by `RUST_LOG`, showing diski's own
+/// info and everything else's warnings when it is unset or malformed.
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(concat!("warn,", env!("CARGO_CRATE_NAME"), "=info")));
+
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .try_init();
+}
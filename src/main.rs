@@ -1,242 +1,4551 @@
-type ExResult<T> = Result<T, Box<dyn std::error::Error + 'static>>;
+type ExResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync + 'static>>;
 
-use std::{collections::HashMap, env::args, future::Future, ops::Not};
+mod auth;
+mod backend;
+mod cli;
+mod clipboard;
+mod config;
+mod control;
+mod dbus;
+mod diagnose;
+mod exit_code;
+mod fstab;
+mod holders;
+mod hooks;
+mod i18n;
+mod idle;
+mod install;
+mod logging;
+mod metrics;
+mod monitor;
+mod mount;
+mod passphrase;
+mod prefs;
+mod remount;
+mod sleep;
+mod space;
+mod state;
+mod status;
+mod udisks;
+mod unit_name;
 
-use futures::StreamExt;
-use ksni::TrayMethods;
-use notify_rust::Notification;
-use tokio::{select, sync::mpsc, try_join};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    os::fd::AsRawFd,
+    path::Path,
+    process::{exit, Stdio},
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use auth::Authorizer;
+use backend::SystemdBackend;
+use cli::Cli;
+use config::{Config, DriveSpec, Icons, Instance, LowSpace, MenuLayout, OnActivate, UnitKind};
+use exit_code::Failure;
+use i18n::tr;
+use space::Space;
+use state::{AutomountState, MountState};
+use udisks::{Health, Identity};
+
+use clap::Parser;
+use futures::{
+    future::{join_all, try_join_all},
+    FutureExt, StreamExt,
+};
+use ksni::{Handle, TrayMethods};
+use notify_rust::{Hint, Notification, NotificationHandle, Timeout, Urgency};
+use tokio::{
+    process::Command,
+    select,
+    signal::unix::{signal, Signal, SignalKind},
+    sync::{mpsc, watch},
+    task::{self, JoinHandle, JoinSet},
+    time, try_join,
+};
+use tracing::{debug, info, warn};
 use zbus::zvariant::OwnedObjectPath;
-use zbus_polkit::policykit1::{AuthorityProxy, CheckAuthorizationFlags, Subject};
-use zbus_systemd::systemd1::{ManagerProxy, UnitProxy};
+use zbus_polkit::policykit1::AuthorityProxy;
+use zbus_systemd::systemd1::ManagerProxy;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct DiskTray {
+    systemd_name: String,
     display_name: String,
+    drive: usize,
+    /// Swaps get their own labels and no automount items.
+    kind: UnitKind,
     mount: MountState,
+    /// What systemd last said the mount unit's sub-state is, `mounting-done` shows
+    /// as finalizing while `mount` already counts it as mounted.
+    mount_substate: String,
     automount: AutomountState,
-    requester: mpsc::UnboundedSender<ClientRequests>,
+    mount_info: MountInfo,
+    /// `None` when the unit file can't be toggled, e.g. generated from fstab
+    automount_at_boot: Option<bool>,
+    identity: Option<Identity>,
+    space: Option<Space>,
+    /// `None` when the drive has no SMART data to show.
+    health: Option<Health>,
+    icons: Icons,
+    bus_connected: bool,
+    /// Actions are refused because polkit isn't there to authorize them.
+    polkit_missing: bool,
+    /// A job queued for one of the drive's units, other actions wait for it.
+    pending_job: Option<PendingJob>,
+    /// A request for the drive is being handled, the icon blinks until it's done.
+    busy: bool,
+    blink: bool,
+    /// The drive's LUKS container has to be unlocked before it can be mounted.
+    locked: bool,
+    /// From the mount table, `None` while not mounted.
+    read_only: Option<bool>,
+    /// When the mount unit last became mounted and dead, since diski started.
+    last_mounted: Option<Instant>,
+    last_unmounted: Option<Instant>,
+    /// The drive's device is plugged in, the icon is hidden while it isn't.
+    present: bool,
+    /// Successes for the drive aren't announced, see [`prefs::Preferences`].
+    quiet: bool,
+    /// Failures to enable automounting in a row, and when the last one
+    /// happened, see [`automount_backoff`].
+    automount_failures: u32,
+    automount_failed_at: Option<Instant>,
+    /// The directory of a drive's icon file, searched before the icon theme.
+    icon_theme_path: String,
+    requester: mpsc::UnboundedSender<(usize, ClientRequests)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum ClientRequests {
+    /// Sent by the menu, turns into `PrepareDisconnect` once confirmed when asked to.
+    RequestDisconnect,
     PrepareDisconnect,
     EnableAutomounting,
+    DisableAutomounting,
+    SetAutomountAtBoot(bool),
+    Mount,
+    Unmount,
+    Remount,
+    /// Offered after an unmount fails, detaches the filesystem through UDisks2.
+    ForceUnmount,
+    /// Stops both units right away, without the in-use check or confirmation.
+    EmergencyDisconnect,
+    /// Disconnects every grouped mount of the physical drive, then powers it off.
+    DisconnectDrive,
+    /// Unlocks the drive's LUKS container, then mounts it.
+    Unlock,
+    /// Remounts the filesystem in place, read-only or back to read-write.
+    SetReadOnly(bool),
+    /// Clears the failed state, which otherwise keeps the unit from starting again.
+    ResetFailed,
+    OpenFolder,
+    /// Copies the mount point, or the backing device when it isn't known.
+    CopyPath,
+    /// Stops or resumes announcing the drive's successes, remembered across restarts.
+    SetQuiet(bool),
+    /// Reads the units' states again, in case a signal went missing.
+    Refresh,
+    /// Mounts every configured drive that isn't mounted, see [`Batch`].
+    MountAll,
+    /// Disconnects every mounted drive.
+    DisconnectAll,
+    /// Reloads the systemd manager after unit files were edited, at most once per [`RELOAD_COOLDOWN`].
+    ReloadDaemon,
+    /// Opens the project's page from the about item.
+    OpenAbout,
+    Quit,
 }
 
-impl ksni::Tray for DiskTray {
-    const MENU_ON_ACTIVATE: bool = true;
+impl ClientRequests {
+    /// Requests like `MountAll` are still sent with a drive, any will do as
+    /// the request isn't about one. The first is picked.
+    fn for_every_drive(self) -> (usize, Self) {
+        (0, self)
+    }
 
-    fn id(&self) -> String {
-        env!("CARGO_PKG_NAME").into()
+    /// Every polkit action the request needs, checked in order before it runs.
+    /// Enabling unit files only takes effect once the daemon has been reloaded,
+    /// and disconnecting also powers the drive off when `power_off` is set.
+    fn polkit_actions(&self, power_off: bool) -> &'static [&'static str] {
+        match self {
+            Self::SetAutomountAtBoot(_) => &[auth::MANAGE_UNIT_FILES, auth::RELOAD_DAEMON],
+            Self::ForceUnmount => &[auth::UNMOUNT_OTHERS],
+            Self::DisconnectDrive => &[auth::MANAGE_UNITS, auth::POWER_OFF_DRIVE],
+            Self::RequestDisconnect | Self::PrepareDisconnect if power_off => {
+                &[auth::MANAGE_UNITS, auth::POWER_OFF_DRIVE]
+            }
+            Self::Unlock => &[auth::UNLOCK_ENCRYPTED, auth::MANAGE_UNITS],
+            Self::ReloadDaemon => &[auth::RELOAD_DAEMON],
+            // Run through pkexec, which checks its own action
+            Self::SetReadOnly(_) => &[],
+            // Handled by the main loop without touching systemd
+            Self::OpenFolder
+            | Self::CopyPath
+            | Self::SetQuiet(_)
+            | Self::Refresh
+            | Self::OpenAbout
+            | Self::Quit => &[],
+            // Spread over the drives as requests of their own
+            Self::MountAll | Self::DisconnectAll => &[],
+            Self::RequestDisconnect
+            | Self::PrepareDisconnect
+            | Self::EnableAutomounting
+            | Self::DisableAutomounting
+            | Self::Mount
+            | Self::Unmount
+            | Self::Remount
+            | Self::EmergencyDisconnect
+            | Self::ResetFailed => &[auth::MANAGE_UNITS],
+        }
     }
-    fn icon_name(&self) -> String {
-        match (self.mount, self.automount) {
-            (MountState::Dead, AutomountState::Dead) => "media-eject",
-            (MountState::Dead, AutomountState::Running) => "media-optical-recordable-symbolic",
-            (MountState::Mounted, _) => "media-optical-data",
-            _ => "media-optical",
+
+    /// Matches the control socket's command names.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::RequestDisconnect | Self::PrepareDisconnect => "disconnect",
+            Self::EnableAutomounting => "enable-automount",
+            Self::DisableAutomounting => "disable-automount",
+            Self::SetAutomountAtBoot(_) => "automount-at-boot",
+            Self::Mount => "mount",
+            Self::Unmount => "unmount",
+            Self::Remount => "remount",
+            Self::ForceUnmount => "force-unmount",
+            Self::EmergencyDisconnect => "emergency-disconnect",
+            Self::DisconnectDrive => "disconnect-drive",
+            Self::Unlock => "unlock",
+            Self::SetReadOnly(true) => "read-only",
+            Self::SetReadOnly(false) => "read-write",
+            Self::ResetFailed => "reset-failed",
+            Self::OpenFolder => "open-folder",
+            Self::CopyPath => "copy-path",
+            Self::SetQuiet(_) => "quiet",
+            Self::Refresh => "refresh",
+            Self::MountAll => "mount-all",
+            Self::DisconnectAll => "disconnect-all",
+            Self::ReloadDaemon => "reload-daemon",
+            Self::OpenAbout => "about",
+            Self::Quit => "quit",
         }
-        .into()
     }
-    fn title(&self) -> String {
-        format!("{} Status", self.display_name)
+
+    fn label(&self) -> String {
+        match self {
+            Self::RequestDisconnect | Self::PrepareDisconnect => tr!("request-disconnect"),
+            Self::EnableAutomounting => tr!("request-enable-automount"),
+            Self::DisableAutomounting => tr!("request-disable-automount"),
+            Self::SetAutomountAtBoot(_) => tr!("request-automount-at-boot"),
+            Self::Mount => tr!("request-mount"),
+            Self::Unmount => tr!("request-unmount"),
+            Self::Remount => tr!("request-remount"),
+            Self::ForceUnmount => tr!("request-force-unmount"),
+            Self::EmergencyDisconnect => tr!("request-emergency-disconnect"),
+            Self::DisconnectDrive => tr!("request-disconnect-drive"),
+            Self::Unlock => tr!("request-unlock"),
+            Self::SetReadOnly(true) => tr!("request-read-only"),
+            Self::SetReadOnly(false) => tr!("request-read-write"),
+            Self::ResetFailed => tr!("request-reset-failed"),
+            Self::OpenFolder => plain_label(tr!("menu-open-folder")),
+            Self::CopyPath => plain_label(tr!("menu-copy-path")),
+            Self::SetQuiet(_) => plain_label(tr!("menu-quiet")),
+            Self::Refresh => plain_label(tr!("menu-refresh")),
+            Self::MountAll => tr!("request-mount-all"),
+            Self::DisconnectAll => tr!("request-disconnect-all"),
+            Self::ReloadDaemon => tr!("request-reload-daemon"),
+            Self::OpenAbout => plain_label(tr!("menu-about", version = env!("CARGO_PKG_VERSION"))),
+            Self::Quit => plain_label(tr!("menu-quit")),
+        }
+    }
+}
+
+impl DiskTray {
+    /// The icon for the drive's state, ranked by how pressing that state is
+    /// so a group's icon can show the partition that needs attention.
+    fn shown_icon(&self) -> (u8, &str) {
+        if !self.bus_connected {
+            return (0, "network-offline");
+        }
+
+        let icons = &self.icons;
+        if self.busy && self.blink {
+            return (1, &icons.busy);
+        }
+        match &self.mount {
+            MountState::Mounting | MountState::Unmounting | MountState::Unknown(_) => {
+                (1, &icons.busy)
+            }
+            MountState::Failed => (2, &icons.failed),
+            MountState::Mounted => (3, &icons.mounted),
+            MountState::Dead => match self.automount {
+                AutomountState::Failed => (2, &icons.failed),
+                AutomountState::Waiting | AutomountState::Running => (4, &icons.armed),
+                _ => (5, &icons.unmounted),
+            },
+        }
     }
 
-    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+    fn is_mounted(&self) -> bool {
+        self.mount == MountState::Mounted || self.automount == AutomountState::Running
+    }
+
+    /// What is left of the wait after failing to enable automounting.
+    fn automount_cooldown(&self) -> Option<Duration> {
+        let failed_at = self.automount_failed_at?;
+        automount_backoff(self.automount_failures)
+            .checked_sub(failed_at.elapsed())
+            .filter(|left| !left.is_zero())
+    }
+
+    /// Whether the menu's actions are available, clicks follow the same rule.
+    fn actions_allowed(&self) -> bool {
+        !self.polkit_missing && self.pending_job.is_none()
+    }
+
+    fn toggle_mount(&mut self) {
+        if !self.actions_allowed() {
+            return;
+        }
+
+        let request = match self.mount {
+            _ if self.is_mounted() => ClientRequests::Unmount,
+            MountState::Dead | MountState::Failed => ClientRequests::Mount,
+            // Mid job, or a state where the right move isn't obvious
+            _ => return,
+        };
+        let _ = self.requester.send((self.drive, request));
+    }
+
+    /// The drive's menu, for its own tray or as a submenu of a shared one.
+    /// Quitting is left to the tray itself when it isn't `standalone`.
+    fn items<T: Member>(&self, standalone: bool) -> Vec<ksni::MenuItem<T>> {
         use ksni::menu::*;
-        vec![
-            StandardItem {
-                label: format!("Mount: {:?}", self.mount),
+
+        let drive = self.drive;
+
+        if !self.bus_connected {
+            return vec![StandardItem {
+                label: tr!("menu-bus-disconnected"),
                 enabled: false,
-                disposition: Disposition::Informative,
+                disposition: Disposition::Alert,
                 ..Default::default()
             }
-            .into(),
+            .into()];
+        }
+
+        let swap = self.kind == UnitKind::Swap;
+        let layout = menu_layout();
+        let state = layout.show_state_items;
+        let mut items = Vec::new();
+        if state {
+            items.push(
+                StandardItem {
+                    label: match self.mount_substate.as_str() {
+                        "mounting-done" | "activating-done" if swap => {
+                            tr!("menu-swap-state", state = tr!("swap-finalizing"))
+                        }
+                        "mounting-done" => {
+                            tr!("menu-mount-state", state = tr!("mount-finalizing"))
+                        }
+                        _ if swap => tr!("menu-swap-state", state = self.mount.swap_label()),
+                        _ => tr!("menu-mount-state", state = self.mount),
+                    },
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+        if state && layout.show_automount_item && !swap {
+            items.push(
+                StandardItem {
+                    label: tr!("menu-automount-state", state = self.automount),
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        let transitions = [
+            self.last_mounted
+                .map(|at| tr!("menu-last-mounted", ago = ago(at.elapsed()))),
+            self.last_unmounted
+                .map(|at| tr!("menu-last-unmounted", ago = ago(at.elapsed()))),
+        ];
+        for label in transitions.into_iter().flatten().filter(|_| state) {
+            items.push(
+                StandardItem {
+                    label,
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        if let Some(path) = self
+            .mount_info
+            .path
+            .as_ref()
+            .filter(|_| layout.show_path_item)
+        {
+            items.push(
+                StandardItem {
+                    label: literal_label(tr!("menu-path", path = path)),
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        if let Some(fs_type) = self.mount_info.fs_type.as_ref().filter(|_| state) {
+            items.push(
+                StandardItem {
+                    label: literal_label(tr!("menu-fs", fs = fs_type)),
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        if let Some(options) = self.mount_info.options.as_ref().filter(|_| state) {
+            items.push(
+                StandardItem {
+                    label: literal_label(tr!("menu-options", options = options)),
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        if let Some(identity) = self.identity.as_ref().filter(|_| state) {
+            let labels = [
+                identity
+                    .label
+                    .as_ref()
+                    .map(|label| literal_label(tr!("menu-label", label = label))),
+                identity
+                    .uuid
+                    .as_ref()
+                    .map(|uuid| literal_label(tr!("menu-uuid", uuid = uuid))),
+                identity
+                    .device
+                    .as_ref()
+                    .map(|device| literal_label(tr!("menu-device", device = device))),
+            ];
+            for label in labels.into_iter().flatten() {
+                items.push(
+                    StandardItem {
+                        label,
+                        enabled: false,
+                        disposition: Disposition::Informative,
+                        ..Default::default()
+                    }
+                    .into(),
+                );
+            }
+        }
+
+        if let Some(space) = self.space.filter(|_| state && self.is_mounted()) {
+            items.push(
+                StandardItem {
+                    label: tr!("menu-free", space = space),
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        // A failing drive is warned about even with the state lines hidden
+        if let Some(health) = self.health.filter(|health| state || health.failing) {
+            let (label, disposition) = match health {
+                Health { failing: true, .. } => (tr!("menu-health-failing"), Disposition::Warning),
+                Health {
+                    temperature: Some(celsius),
+                    ..
+                } => (
+                    tr!("menu-health-ok-temperature", celsius = celsius.round()),
+                    Disposition::Informative,
+                ),
+                Health { .. } => (tr!("menu-health-ok"), Disposition::Informative),
+            };
+            items.push(
+                StandardItem {
+                    label,
+                    enabled: false,
+                    disposition,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        if self.polkit_missing {
+            items.push(
+                StandardItem {
+                    label: tr!("menu-polkit-missing"),
+                    enabled: false,
+                    disposition: Disposition::Alert,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        if let Some(job) = &self.pending_job {
+            items.push(
+                StandardItem {
+                    label: job.kind.to_string(),
+                    enabled: false,
+                    disposition: Disposition::Informative,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+
+        let actions = self.actions_allowed();
+        // Only enabling is held back after it failed, disabling never is
+        let enabling = !matches!(
+            self.automount,
+            AutomountState::Waiting | AutomountState::Running
+        );
+        let cooldown = self.automount_cooldown().filter(|_| enabling);
+        if self.mount == MountState::Failed || self.automount == AutomountState::Failed {
+            items.push(
+                StandardItem {
+                    label: action_label(tr!("menu-reset-failed")),
+                    enabled: actions,
+                    disposition: Disposition::Warning,
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray
+                            .requester
+                            .send((tray.drive, ClientRequests::ResetFailed));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+        if self.locked {
+            items.push(
+                StandardItem {
+                    label: action_label(tr!("menu-unlock")),
+                    enabled: actions,
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray.requester.send((tray.drive, ClientRequests::Unlock));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+        if swap {
+            items.extend(self.swap_items(actions));
+            items.push(advanced_item(drive, actions));
+            if standalone {
+                items.extend(about_item(drive));
+                items.push(quit_item(drive));
+            }
+            return items;
+        }
+        let mut action_items: Vec<(&str, MenuItem<T>)> = vec![
+            (
+                "open-folder",
+                StandardItem {
+                    label: action_label(tr!("menu-open-folder")),
+                    enabled: self.is_mounted() && self.mount_info.path.is_some(),
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray
+                            .requester
+                            .send((tray.drive, ClientRequests::OpenFolder));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "copy-path",
+                StandardItem {
+                    label: action_label(tr!("menu-copy-path")),
+                    enabled: self.mount_info.path.is_some() || self.mount_info.what.is_some(),
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray.requester.send((tray.drive, ClientRequests::CopyPath));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "disconnect",
+                StandardItem {
+                    label: action_label(tr!("menu-disconnect")),
+                    enabled: actions,
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray
+                            .requester
+                            .send((tray.drive, ClientRequests::RequestDisconnect));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "mount",
+                StandardItem {
+                    label: action_label(tr!("menu-mount")),
+                    enabled: actions
+                        && !matches!(self.mount, MountState::Mounted | MountState::Mounting),
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray.requester.send((tray.drive, ClientRequests::Mount));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "unmount",
+                StandardItem {
+                    label: action_label(tr!("menu-unmount")),
+                    enabled: actions && self.is_mounted(),
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray.requester.send((tray.drive, ClientRequests::Unmount));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "remount",
+                StandardItem {
+                    label: action_label(tr!("menu-remount")),
+                    enabled: actions && self.mount == MountState::Mounted,
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray.requester.send((tray.drive, ClientRequests::Remount));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "read-only",
+                StandardItem {
+                    label: if self.read_only == Some(true) {
+                        action_label(tr!("menu-remount-read-write"))
+                    } else {
+                        action_label(tr!("menu-remount-read-only"))
+                    },
+                    enabled: actions
+                        && self.mount == MountState::Mounted
+                        && self.read_only.is_some(),
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        if let Some(read_only) = tray.read_only {
+                            let _ = tray
+                                .requester
+                                .send((tray.drive, ClientRequests::SetReadOnly(!read_only)));
+                        }
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "automount",
+                CheckmarkItem {
+                    label: match cooldown {
+                        Some(left) => action_label(tr!(
+                            "menu-automount-cooldown",
+                            seconds = left.as_secs() + 1
+                        )),
+                        None => action_label(tr!("menu-automount")),
+                    },
+                    enabled: actions && cooldown.is_none(),
+                    checked: self.automount != AutomountState::Dead,
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        // A failed automount is worth starting again rather than stopping
+                        let request = match tray.automount {
+                            AutomountState::Waiting | AutomountState::Running => {
+                                ClientRequests::DisableAutomounting
+                            }
+                            _ => ClientRequests::EnableAutomounting,
+                        };
+                        let _ = tray.requester.send((tray.drive, request));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "automount-at-boot",
+                CheckmarkItem {
+                    label: action_label(tr!("menu-automount-at-boot")),
+                    enabled: actions && self.automount_at_boot.is_some(),
+                    checked: self.automount_at_boot == Some(true),
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        if let Some(enabled) = tray.automount_at_boot {
+                            let _ = tray
+                                .requester
+                                .send((tray.drive, ClientRequests::SetAutomountAtBoot(!enabled)));
+                        }
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            (
+                "quiet",
+                CheckmarkItem {
+                    label: action_label(tr!("menu-quiet")),
+                    checked: self.quiet,
+                    activate: Box::new(move |tray: &mut T| {
+                        let Some(tray) = tray.member(drive) else {
+                            return;
+                        };
+                        let _ = tray
+                            .requester
+                            .send((tray.drive, ClientRequests::SetQuiet(!tray.quiet)));
+                    }),
+                    ..Default::default()
+                }
+                .into(),
+            ),
+        ];
+        // Only ever the automount checkmarks, so the menu keeps mounting and disconnecting
+        if !layout.show_automount_item {
+            action_items.retain(|(name, _)| !name.starts_with("automount"));
+        }
+        // Stable, so whatever menu_order leaves out keeps its usual place after it
+        action_items.sort_by_key(|(name, _)| {
+            layout
+                .order
+                .iter()
+                .position(|first| first == name)
+                .unwrap_or(layout.order.len())
+        });
+        items.extend(action_items.into_iter().map(|(_, item)| item));
+        items.extend([
+            MenuItem::Separator,
             StandardItem {
-                label: format!("Automount: {:?}", self.automount),
-                enabled: false,
-                disposition: Disposition::Informative,
+                label: action_label(tr!("menu-emergency-disconnect")),
+                enabled: !self.polkit_missing,
+                disposition: Disposition::Warning,
+                activate: Box::new(move |tray: &mut T| {
+                    let Some(tray) = tray.member(drive) else {
+                        return;
+                    };
+                    let _ = tray
+                        .requester
+                        .send((tray.drive, ClientRequests::EmergencyDisconnect));
+                }),
                 ..Default::default()
             }
             .into(),
+            advanced_item(drive, actions),
+        ]);
+        if standalone {
+            items.extend(about_item(drive));
+            items.push(quit_item(drive));
+        }
+
+        items
+    }
+
+    /// A swap's actions, which turn it on and off and have nothing to open.
+    fn swap_items<T: Member>(&self, actions: bool) -> Vec<ksni::MenuItem<T>> {
+        use ksni::menu::*;
+
+        let drive = self.drive;
+        vec![
             StandardItem {
-                label: "Disconnect".into(),
-                activate: Box::new(|tray: &mut Self| {
-                    let _ = tray.requester.send(ClientRequests::PrepareDisconnect);
+                label: action_label(tr!("menu-swap-on")),
+                enabled: actions
+                    && !matches!(self.mount, MountState::Mounted | MountState::Mounting),
+                activate: Box::new(move |tray: &mut T| {
+                    let Some(tray) = tray.member(drive) else {
+                        return;
+                    };
+                    let _ = tray.requester.send((tray.drive, ClientRequests::Mount));
                 }),
                 ..Default::default()
             }
             .into(),
             StandardItem {
-                label: "Enable automount".into(),
-                activate: Box::new(|tray: &mut Self| {
-                    let _ = tray.requester.send(ClientRequests::EnableAutomounting);
+                label: action_label(tr!("menu-swap-off")),
+                enabled: actions && self.is_mounted(),
+                activate: Box::new(move |tray: &mut T| {
+                    let Some(tray) = tray.member(drive) else {
+                        return;
+                    };
+                    let _ = tray.requester.send((tray.drive, ClientRequests::Unmount));
                 }),
                 ..Default::default()
             }
             .into(),
+            MenuItem::Separator,
         ]
     }
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> ExResult<()> {
-    let mut args = args().skip(1);
-    let systemd_name = args.next().expect("Expected drive name!");
+/// What drive menus show and in which order, set once from the config.
+static MENU: OnceLock<MenuLayout> = OnceLock::new();
 
-    let display_name = args.next().expect("Expected name!");
+fn menu_layout() -> &'static MenuLayout {
+    MENU.get_or_init(MenuLayout::default)
+}
+
+/// Whether menu labels mark their access keys, set once from `menu_mnemonics`.
+static MNEMONICS: OnceLock<bool> = OnceLock::new();
 
-    let conn = zbus::Connection::system().await?;
+fn mnemonics() -> bool {
+    MNEMONICS.get() == Some(&true)
+}
 
-    let mount_name = format!("{systemd_name}.mount");
-    let automount_name = format!("{systemd_name}.automount");
+/// An action's label, which marks its access key with `_` in the catalogue.
+fn action_label(label: String) -> String {
+    if mnemonics() {
+        label
+    } else {
+        plain_label(label)
+    }
+}
 
-    let authority = AuthorityProxy::new(&conn).await?;
-    let subject = Subject::new_for_owner(std::process::id(), None, None)?;
+/// Without the access key, for hosts that would show the `_` and outside menus.
+fn plain_label(label: String) -> String {
+    label.replacen('_', "", 1)
+}
 
-    let manager = zbus_systemd::systemd1::ManagerProxy::new(&conn).await?;
-    let mount = manager.get_unit(mount_name.clone()).await?;
-    let automount = manager.get_unit(automount_name.clone()).await?;
+/// Shown as it is, so underscores in paths and labels aren't taken for
+/// access keys once those are read.
+fn literal_label(label: String) -> String {
+    if mnemonics() {
+        label.replace('_', "__")
+    } else {
+        label
+    }
+}
 
-    let mount = UnitProxy::new(&conn, mount).await?;
-    let automount = UnitProxy::new(&conn, automount).await?;
+/// How long ago, coarsely. Menus are rebuilt with every tray update, which
+/// keeps this fresh enough.
+fn ago(elapsed: Duration) -> String {
+    let minutes = elapsed.as_secs() / 60;
+    match minutes {
+        0 => tr!("ago-now"),
+        1..=59 => tr!("ago-minutes", n = minutes),
+        60..=1439 => tr!("ago-hours", n = minutes / 60),
+        _ => tr!("ago-days", n = minutes / 1440),
+    }
+}
 
-    let mut mount_state = MountState::from_substates(&mount.sub_state().await?);
-    let mut automount_state = AutomountState::from_substates(&automount.sub_state().await?);
+/// How long enabling automounting stays off after `failures` in a row.
+fn automount_backoff(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    AUTOMOUNT_BACKOFF
+        .saturating_mul(1 << doublings)
+        .min(MAX_AUTOMOUNT_BACKOFF)
+}
 
-    let (sender, mut events) = mpsc::unbounded_channel();
+/// Rarely needed actions that aren't about the drive itself.
+fn advanced_item<T: Member>(drive: usize, enabled: bool) -> ksni::MenuItem<T> {
+    use ksni::menu::*;
 
-    let tray = DiskTray {
-        display_name,
-        mount: mount_state,
-        automount: automount_state,
-        requester: sender,
-    };
+    SubMenu {
+        label: action_label(tr!("menu-advanced")),
+        submenu: vec![
+            StandardItem {
+                label: action_label(tr!("menu-refresh")),
+                activate: Box::new(move |tray: &mut T| {
+                    let Some(tray) = tray.member(drive) else {
+                        return;
+                    };
+                    let _ = tray.requester.send((tray.drive, ClientRequests::Refresh));
+                }),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: action_label(tr!("menu-reload-daemon")),
+                enabled,
+                activate: Box::new(move |tray: &mut T| {
+                    let Some(tray) = tray.member(drive) else {
+                        return;
+                    };
+                    let _ = tray
+                        .requester
+                        .send((tray.drive, ClientRequests::ReloadDaemon));
+                }),
+                ..Default::default()
+            }
+            .into(),
+        ],
+        ..Default::default()
+    }
+    .into()
+}
 
-    let handle = tray.spawn().await.unwrap();
+/// The version, opening the repository when Cargo.toml names one. Left out
+/// with `show_about_item = false`.
+fn about_item<T: Member>(drive: usize) -> Option<ksni::MenuItem<T>> {
+    if !menu_layout().show_about_item {
+        return None;
+    }
+    Some(
+        ksni::menu::StandardItem {
+            label: action_label(tr!("menu-about", version = env!("CARGO_PKG_VERSION"))),
+            enabled: !env!("CARGO_PKG_REPOSITORY").is_empty(),
+            activate: Box::new(move |tray: &mut T| {
+                let Some(tray) = tray.member(drive) else {
+                    return;
+                };
+                let _ = tray.requester.send((tray.drive, ClientRequests::OpenAbout));
+            }),
+            ..Default::default()
+        }
+        .into(),
+    )
+}
 
-    let mut mount_state_change = mount.receive_sub_state_changed().await;
-    let mut automount_state_change = automount.receive_sub_state_changed().await;
+fn quit_item<T: Member>(drive: usize) -> ksni::MenuItem<T> {
+    ksni::menu::StandardItem {
+        label: action_label(tr!("menu-quit")),
+        activate: Box::new(move |tray: &mut T| {
+            let Some(tray) = tray.member(drive) else {
+                return;
+            };
+            let _ = tray.requester.send((tray.drive, ClientRequests::Quit));
+        }),
+        ..Default::default()
+    }
+    .into()
+}
 
-    manager.subscribe().await?;
+/// A tray showing one or more drives, so menu items can find the one they act on.
+trait Member: Send + 'static {
+    fn member(&mut self, drive: usize) -> Option<&mut DiskTray>;
+}
 
-    loop {
-        select! {
-            biased;
-            s = mount_state_change.next()  => {
-                let new = MountState::from_substates(&s.unwrap().get().await.unwrap());
+impl Member for DiskTray {
+    fn member(&mut self, _drive: usize) -> Option<&mut DiskTray> {
+        Some(self)
+    }
+}
 
-                if new != mount_state {
-                    mount_state = new;
-                    handle.update(|t| t.mount = dbg!(mount_state)).await;
-                }
-            }
-            s = automount_state_change.next() => {
-                let new = AutomountState::from_substates(&s.unwrap().get().await.unwrap());
+/// One icon for every configured mount on the same physical drive, each in
+/// its own submenu below the actions for the drive as a whole.
+#[derive(Clone)]
+struct GroupTray {
+    /// The drive's vendor and model, when UDisks2 knows them.
+    name: String,
+    members: Vec<DiskTray>,
+}
 
-                if new != automount_state {
-                    automount_state = new;
-                    handle.update(|t| t.automount = dbg!(automount_state)).await;
-                }
-            }
-            Some(req) = events.recv() => {
-                let result = authority
-                    .check_authorization(
-                        &subject,
-                        "org.freedesktop.systemd1.manage-units",
-                        &HashMap::default(),
-                        CheckAuthorizationFlags::AllowUserInteraction.into(),
-                        "",
-                    )
-                    .await?;
+impl Member for GroupTray {
+    fn member(&mut self, drive: usize) -> Option<&mut DiskTray> {
+        self.members.iter_mut().find(|member| member.drive == drive)
+    }
+}
 
-                if result.is_authorized.not() {
-                    continue;
-                }
+impl GroupTray {
+    /// The partition whose state is the most pressing, which the icon shows.
+    fn most_pressing(&self) -> Option<&DiskTray> {
+        self.members
+            .iter()
+            .min_by_key(|member| member.shown_icon().0)
+    }
+}
 
-                match req {
-                    ClientRequests::PrepareDisconnect => {
-                        try_join!(
-                            job_wait(&manager, automount.stop("replace".into())),
-                            job_wait(&manager, mount.stop("replace".into()))
-                        )?;
-
-                        Notification::new()
-                            .summary(&systemd_name)
-                            .body("Drive has been fully unmounted")
-                            .icon("drive-harddisk")
-                            .show_async().await?;
-                    },
-                    ClientRequests::EnableAutomounting => {
-                        job_wait(&manager, automount.start("replace".into())).await?;
-
-                        Notification::new()
-                            .summary(&systemd_name)
-                            .body("Automounting has been enabled")
-                            .icon("drive-harddisk")
-                            .show_async().await?;
-                    },
+impl ksni::Tray for GroupTray {
+    const MENU_ON_ACTIVATE: bool = true;
+
+    fn id(&self) -> String {
+        let ids: Vec<String> = self.members.iter().map(ksni::Tray::id).collect();
+        ids.join("_")
+    }
+
+    /// The shown partition's, its icon may be a file in a directory of its own.
+    fn icon_theme_path(&self) -> String {
+        self.most_pressing()
+            .map(ksni::Tray::icon_theme_path)
+            .unwrap_or_default()
+    }
+
+    fn icon_name(&self) -> String {
+        self.most_pressing()
+            .map(ksni::Tray::icon_name)
+            .unwrap_or_default()
+    }
+
+    /// Hidden once none of the partitions' devices are plugged in.
+    fn status(&self) -> ksni::Status {
+        if self.members.iter().any(|member| member.present) {
+            ksni::Status::Active
+        } else {
+            ksni::Status::Passive
+        }
+    }
+
+    fn title(&self) -> String {
+        if !self.name.is_empty() {
+            return self.name.clone();
+        }
+        let names: Vec<&str> = self
+            .members
+            .iter()
+            .map(|member| member.display_name.as_str())
+            .collect();
+        names.join(", ")
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        use ksni::menu::*;
+
+        let Some(first) = self.members.first() else {
+            return Vec::new();
+        };
+        let drive = first.drive;
+        let actions = self.members.iter().all(|member| {
+            member.bus_connected && !member.polkit_missing && member.pending_job.is_none()
+        });
+
+        let mut items: Vec<MenuItem<Self>> = vec![StandardItem {
+            label: action_label(tr!("menu-disconnect-drive")),
+            enabled: actions,
+            activate: Box::new(move |tray: &mut Self| {
+                if let Some(member) = tray.member(drive) {
+                    let _ = member
+                        .requester
+                        .send((drive, ClientRequests::DisconnectDrive));
                 }
-            }
+            }),
+            ..Default::default()
+        }
+        .into()];
+        items.push(MenuItem::Separator);
+        for member in &self.members {
+            items.push(
+                SubMenu {
+                    label: literal_label(member.title()),
+                    icon_name: member.icon_name(),
+                    submenu: member.items(false),
+                    ..Default::default()
+                }
+                .into(),
+            );
         }
+        items.push(MenuItem::Separator);
+        items.extend(about_item(drive));
+        items.push(quit_item(drive));
+
+        items
     }
 }
 
-async fn job_wait(
-    manager: &ManagerProxy<'_>,
-    job_future: impl Future<Output = zbus::Result<OwnedObjectPath>>,
-) -> ExResult<()> {
-    let mut removed_stream = manager.receive_job_removed().await?;
+impl ksni::Tray for DiskTray {
+    const MENU_ON_ACTIVATE: bool = true;
+
+    /// Distinct per drive so several icons don't replace each other, limited to
+    /// characters that are safe in D-Bus names and paths.
+    fn id(&self) -> String {
+        format!(
+            "{}_{}",
+            env!("CARGO_PKG_NAME"),
+            unit_name::identifier(&self.systemd_name)
+        )
+    }
+    fn icon_theme_path(&self) -> String {
+        self.icon_theme_path.clone()
+    }
+    fn icon_name(&self) -> String {
+        self.shown_icon().1.to_owned()
+    }
+    /// Hosts hide passive items, or tuck them away in an overflow.
+    fn status(&self) -> ksni::Status {
+        if self.present {
+            ksni::Status::Active
+        } else {
+            ksni::Status::Passive
+        }
+    }
+    fn title(&self) -> String {
+        let name = &self.display_name;
+        match &self.mount_info.path {
+            Some(path) => tr!("tray-title-path", name = name, path = path),
+            None => tr!("tray-title", name = name),
+        }
+    }
 
-    let job = job_future.await?;
+    /// Middle click on most hosts, left click opens the menu through
+    /// `MENU_ON_ACTIVATE` and right click always does.
+    fn secondary_activate(&mut self, _x: i32, _y: i32) {
+        self.toggle_mount();
+    }
 
-    loop {
-        let removed = removed_stream.next().await.unwrap();
-        if removed.args()?.job == job {
-            return Ok(());
+    fn tool_tip(&self) -> ksni::ToolTip {
+        ksni::ToolTip {
+            title: self.title(),
+            description: match (&self.pending_job, self.polkit_missing) {
+                (_, true) => tr!("tooltip-polkit-missing"),
+                (Some(job), false) => job.kind.to_string(),
+                (None, false) => String::new(),
+            },
+            ..Default::default()
         }
     }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        self.items(true)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum MountState {
-    Mounted,
-    Mounting,
-    Unmounting,
-    Dead,
-    Failed,
+/// A drive's tray whose left click acts instead of opening the menu, which
+/// is then left to the right click. `MENU_ON_ACTIVATE` can't vary per value.
+#[derive(Clone)]
+struct ClickTray {
+    tray: DiskTray,
+    action: OnActivate,
+}
+
+impl Member for ClickTray {
+    fn member(&mut self, _drive: usize) -> Option<&mut DiskTray> {
+        Some(&mut self.tray)
+    }
 }
 
-impl MountState {
-    fn from_substates(input: &str) -> Self {
-        match input {
-            "mounted" | "mounting-done" => Self::Mounted,
-            "mounting" => Self::Mounting,
-            "unmounting" => Self::Unmounting,
-            "dead" => Self::Dead,
-            "failed" => Self::Failed,
-            input => panic!("Unexpected active state: {input}"),
+impl ksni::Tray for ClickTray {
+    const MENU_ON_ACTIVATE: bool = false;
+
+    fn id(&self) -> String {
+        self.tray.id()
+    }
+
+    fn icon_theme_path(&self) -> String {
+        self.tray.icon_theme_path()
+    }
+
+    fn icon_name(&self) -> String {
+        self.tray.icon_name()
+    }
+
+    fn status(&self) -> ksni::Status {
+        self.tray.status()
+    }
+
+    fn title(&self) -> String {
+        self.tray.title()
+    }
+
+    fn activate(&mut self, _x: i32, _y: i32) {
+        match self.action {
+            OnActivate::ToggleMount => self.tray.toggle_mount(),
+            OnActivate::Disconnect if self.tray.actions_allowed() => {
+                let _ = self
+                    .tray
+                    .requester
+                    .send((self.tray.drive, ClientRequests::RequestDisconnect));
+            }
+            OnActivate::Disconnect | OnActivate::Menu => {}
         }
     }
+
+    fn secondary_activate(&mut self, x: i32, y: i32) {
+        self.tray.secondary_activate(x, y);
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        self.tray.tool_tip()
+    }
+
+    fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
+        self.tray.items(true)
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum AutomountState {
-    Dead,
-    Waiting,
-    Running,
-    Failed,
-}
-
-impl AutomountState {
-    fn from_substates(input: &str) -> Self {
-        match input {
-            "dead" => Self::Dead,
-            "waiting" => Self::Waiting,
-            "running" => Self::Running,
-            "failed" => Self::Failed,
-            input => panic!("Unexpected active state: {input}"),
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExResult<()> {
+    logging::init();
+
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Help and the version go to stdout and succeed
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            let _ = e.print();
+            exit(exit_code::FAILED);
+        }
+    };
+
+    let failed = |e: Failure| -> ! {
+        eprintln!("{}: {e}", env!("CARGO_PKG_NAME"));
+        exit(e.code());
+    };
+    match cli.command {
+        Some(cli::Command::Status(args)) => return status::run(args).await.or_else(|e| failed(e)),
+        Some(cli::Command::Watch(args)) => return monitor::run(args).await.or_else(|e| failed(e)),
+        Some(cli::Command::Mount(args)) => return mount::run(args).await.or_else(|e| failed(e)),
+        Some(cli::Command::Diagnose(args)) => {
+            return diagnose::run(args).await.or_else(|e| failed(e))
+        }
+        Some(cli::Command::Install(args)) => {
+            return install::install(args).await.or_else(|e| failed(e.into()))
+        }
+        Some(cli::Command::Uninstall(args)) => {
+            return install::uninstall(args).await.or_else(|e| failed(e.into()))
+        }
+        None => {}
+    }
+
+    let config = Config::load(cli.tray).unwrap_or_else(|e| {
+        eprintln!("{}: {e}", env!("CARGO_PKG_NAME"));
+        exit(exit_code::FAILED);
+    });
+
+    let job_timeout = config.job_timeout;
+    let opener = config.opener;
+    let _ = NOTIFICATIONS.set(NotificationSettings {
+        timeout: config.notification_timeout,
+        icon_success: config.notify_icon_success,
+        icon_error: config.notify_icon_error,
+        sound: config.notify_sound,
+    });
+    let _ = MNEMONICS.set(config.menu_mnemonics);
+    let _ = MENU.set(config.menu.clone());
+
+    let mut shutdown = Shutdown::listen()?;
+
+    let mut bus = select! {
+        bus = Bus::connect_with_backoff(config.instance, config.bus_address.clone(), config.poll_interval) => bus,
+        _ = shutdown.requested() => return Ok(()),
+    };
+
+    let authorizer =
+        Arc::new(Authorizer::new(&bus.system, config.auth_cache_ttl, config.polkit_subject).await?);
+
+    let mut specs = Vec::with_capacity(config.drives.len());
+    let mut not_found = Vec::new();
+    for spec in config.drives {
+        let name = &spec.systemd_name;
+        let missing = match Units::missing(&bus.manager, name, spec.kind).await {
+            Ok(missing) => missing,
+            Err(e) => {
+                warn!("Could not check the units of {name}: {e}");
+                specs.push(spec);
+                continue;
+            }
+        };
+
+        if spec.discovered && !missing.is_empty() {
+            info!(
+                "Skipping {name} from fstab, it has no {}",
+                missing.join(" or ")
+            );
+            continue;
+        }
+
+        let problem = match missing.as_slice() {
+            [] => {
+                specs.push(spec);
+                continue;
+            }
+            [mount, automount] => format!(
+                "No systemd units '{mount}' or '{automount}' found — is the drive configured in fstab?"
+            ),
+            [unit] if unit.ends_with(".swap") => format!(
+                "No systemd unit '{unit}' found — is the swap configured in fstab?"
+            ),
+            [unit] if unit.ends_with(".automount") => format!(
+                "No systemd unit '{unit}' found, only '{name}.mount' — does its fstab entry have x-systemd.automount?"
+            ),
+            [unit, ..] => format!(
+                "No systemd unit '{unit}' found, only '{name}.automount' — is the drive configured in fstab?"
+            ),
+        };
+        // The other drives are still worth running for
+        warn!("Skipping {name}: {problem}");
+        not_found.push(problem);
+    }
+    if specs.is_empty() && !not_found.is_empty() {
+        for problem in not_found {
+            eprintln!("{}: {problem}", env!("CARGO_PKG_NAME"));
+        }
+        // Told apart from other startup failures so setup scripts can point at the config
+        exit(exit_code::UNIT_NOT_FOUND);
+    }
+
+    let (sender, mut events) = mpsc::unbounded_channel();
+    let (changes_sender, mut changes) = mpsc::unbounded_channel();
+
+    // A dry run never asks polkit, so it has nothing to be blocked on
+    let allow_without_polkit = config.allow_without_polkit || config.dry_run;
+    let mut actions_blocked = bus.polkit_missing && !allow_without_polkit;
+
+    let mut prefs = prefs::Preferences::load();
+    let mut drives = Vec::with_capacity(specs.len());
+    let mut trays = Vec::with_capacity(specs.len());
+    for spec in specs {
+        let index = drives.len();
+        let name = spec.systemd_name.clone();
+        let icons = config.tray.then(|| config.icons.clone());
+        match Drive::connect(
+            &bus,
+            spec,
+            icons,
+            index,
+            sender.clone(),
+            changes_sender.clone(),
+        )
+        .await
+        {
+            Ok((mut drive, mut tray)) => {
+                // Set from the tray, the config only decides until then
+                drive.quiet = prefs.quiet(&name).unwrap_or(!config.notify_on_success);
+                if let Some(tray) = &mut tray {
+                    tray.polkit_missing = actions_blocked;
+                    tray.quiet = drive.quiet;
+                }
+                drives.push(drive);
+                trays.push(tray);
+            }
+            Err(e) => warn!("Skipping {name}: {e}"),
+        }
+    }
+    // Don't make every following drive wait for a host that isn't coming
+    if !show_trays(
+        &mut drives,
+        trays,
+        config.group_partitions,
+        config.on_activate,
+    )
+    .await
+    {
+        warn!("Running without tray icons");
+    }
+
+    if drives.is_empty() {
+        return Err("None of the configured drives could be set up".into());
+    }
+
+    let (status, status_updates) = watch::channel(drives.iter().map(Drive::status).collect());
+    let names: Vec<String> = drives.iter().map(|d| d.systemd_name.clone()).collect();
+    let _dbus = match dbus::serve(names.clone(), status_updates.clone(), sender.clone()).await {
+        Ok(conn) => Some(conn),
+        Err(e) => {
+            warn!("Could not export the drives on the session bus: {e}");
+            None
+        }
+    };
+    let operations = Arc::new(metrics::Operations::default());
+    if let Some(addr) = config.metrics_addr {
+        let serving = metrics::serve(
+            addr,
+            names.clone(),
+            status_updates.clone(),
+            operations.clone(),
+        );
+        match serving.await {
+            Ok(()) => info!("Serving metrics on http://{addr}/metrics"),
+            Err(e) => warn!("Could not serve metrics on {addr}: {e}"),
+        }
+    }
+
+    let _control = match control::listen(names, status_updates, sender.clone()) {
+        Ok(socket) => Some(socket),
+        // Without a tray the socket is the only way to send requests
+        Err(e) if drives.iter().any(|d| d.handle.is_none()) => {
+            return Err(format!("Could not open the control socket: {e}").into());
+        }
+        Err(e) => {
+            warn!("Could not open the control socket: {e}");
+            None
+        }
+    };
+
+    // One timer reads free space for every drive, rather than one per drive
+    let mut space_refresh = time::interval(config.space_interval);
+    space_refresh.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut space_poll: Option<JoinHandle<()>> = None;
+    // At most one request per drive runs at a time so they can't conflict,
+    // the ones sent meanwhile wait their turn with duplicates dropped
+    let mut jobs = JoinSet::new();
+    let mut running = HashMap::new();
+    let mut queued: HashMap<usize, VecDeque<ClientRequests>> = HashMap::new();
+    let mut ready = VecDeque::new();
+    let mut last_reload = None;
+    let mut batch: Option<Batch> = None;
+
+    let mut sleep_delay = None;
+    let mut sleep_signals = futures::stream::pending().boxed();
+    if config.inhibit_sleep {
+        match sleep::sleep_signals(&bus.system).await {
+            Ok(signals) => {
+                sleep_signals = signals.boxed();
+                sleep_delay = sleep::inhibit(&bus.system, "delay", &tr!("inhibit-sync")).await;
+            }
+            Err(e) => warn!("Could not watch for the system going to sleep: {e}"),
+        }
+    }
+
+    loop {
+        select! {
+            biased;
+            _ = shutdown.requested() => break,
+            Some((index, change)) = changes.recv() => {
+                let drive = &mut drives[index];
+
+                match change {
+                    StateChange::Mount(new) if drive.mount_state.changed_to(&new) => {
+                        info!(unit = %drive.mount_name(), old = ?drive.mount_state, ?new, "State changed");
+                        drive.mount_state = new.clone();
+                        let now = Instant::now();
+                        drive.update_tray(|t| {
+                            match new {
+                                MountState::Mounted => t.last_mounted = Some(now),
+                                MountState::Dead => t.last_unmounted = Some(now),
+                                _ => {}
+                            }
+                            t.mount = new;
+                        })
+                        .await;
+
+                        if drive.mount_state == MountState::Mounted {
+                            // The options in effect are only known once mounted
+                            let info = drive.units.mount_info().await;
+                            drive.mount_info = info.clone();
+                            drive.update_tray(|t| t.mount_info = info).await;
+                            drive.check_mount_options();
+                            drive.refresh_space().await;
+                            // The device may not have been plugged in at the last lookup
+                            drive.identity_source = None;
+                            drive.refresh_identity();
+                        }
+                        drive.refresh_lock();
+                        drive.refresh_read_only();
+                        drive.update_idle_unmount(index);
+                        drive.reset_low_space();
+
+                        let hooks = &config.hooks;
+                        let hook = match drive.mount_state {
+                            MountState::Mounted => hooks.on_mounted.as_deref(),
+                            MountState::Dead => hooks.on_unmounted.as_deref(),
+                            MountState::Failed => hooks.on_failed.as_deref(),
+                            _ => None,
+                        };
+                        if let Some(hook) = hook.filter(|_| hooks.enabled) {
+                            let path = drive.mount_info.path.as_deref();
+                            hooks::run(hook, &drive.systemd_name, path, hooks.timeout, config.dry_run);
+                        }
+                    }
+                    StateChange::MountSubState(raw) => {
+                        drive.update_tray(|t| t.mount_substate = raw).await;
+                    }
+                    StateChange::Automount(new) if drive.automount_state.changed_to(&new) => {
+                        info!(unit = %drive.automount_name(), old = ?drive.automount_state, ?new, "State changed");
+                        drive.automount_state = new.clone();
+                        drive.update_tray(|t| t.automount = new).await;
+                    }
+                    StateChange::MountInfo(info) => {
+                        let moved = info.path != drive.mount_info.path;
+                        drive.mount_info = info.clone();
+                        drive.update_tray(|t| t.mount_info = info).await;
+                        drive.refresh_identity();
+                        drive.refresh_lock();
+                        drive.refresh_read_only();
+                        if moved {
+                            drive.reset_low_space();
+                        }
+                        drive.update_device_watch(&bus, index, &changes_sender);
+                        drive.check_mount_options();
+                    }
+                    StateChange::Space(space) => {
+                        drive.update_tray(|t| t.space = Some(space)).await;
+                        drive.check_low_space(space);
+                    }
+                    StateChange::Job(job) => {
+                        drive.update_tray(|t| t.pending_job = job).await;
+                    }
+                    StateChange::AutomountAtBoot(enabled) => {
+                        drive.update_tray(|t| t.automount_at_boot = enabled).await;
+                    }
+                    StateChange::Present(present) => {
+                        if !present && drive.mount_state == MountState::Mounted {
+                            warn!(unit = %drive.mount_name(), "The device was removed while mounted");
+                            let name = drive.systemd_name.clone();
+                            let body = tr!("notify-device-vanished");
+                            tokio::spawn(async move { notify_failure(&name, &body).await });
+                        }
+                        drive.update_tray(|t| t.present = present).await;
+                        drive.update_health_watch(present).await;
+                    }
+                    StateChange::BusLost => {
+                        warn!("Lost connection to the {} bus, reconnecting", bus.instance);
+
+                        for drive in &drives {
+                            drive.watcher.abort();
+                            if let Some(watch) = &drive.device_watch {
+                                watch.abort();
+                            }
+                            drive.update_tray(|t| t.bus_connected = false).await;
+                        }
+                        while changes.try_recv().is_ok() {}
+
+                        bus = select! {
+                            bus = Bus::reconnect(bus.instance, bus.address.clone(), bus.poll_interval, &mut drives, &changes_sender) => bus,
+                            _ = shutdown.requested() => break,
+                        };
+
+                        actions_blocked = bus.polkit_missing && !allow_without_polkit;
+                        for drive in &drives {
+                            drive.update_tray(|t| t.polkit_missing = actions_blocked).await;
+                        }
+                    }
+                    _ => {}
+                }
+
+                status.send_replace(drives.iter().map(Drive::status).collect());
+            }
+            Some(sleeping) = sleep_signals.next() => {
+                if sleeping {
+                    let mount_points = drives
+                        .iter()
+                        .filter(|d| d.mount_state == MountState::Mounted)
+                        .filter_map(|d| d.mount_info.path.clone())
+                        .collect();
+                    sync_before_sleep(mount_points, sleep_delay.take());
+                } else if sleep_delay.is_none() {
+                    sleep_delay = sleep::inhibit(&bus.system, "delay", &tr!("inhibit-sync")).await;
+                }
+            }
+            _ = space_refresh.tick() => {
+                // Skipped while the last tick's reads still hang on a stuck filesystem
+                if space_poll.as_ref().is_none_or(|poll| poll.is_finished()) {
+                    space_poll = Some(poll_space(&drives, &changes_sender));
+                }
+            }
+            Some((index, req)) = next_request(&mut ready, &mut events) => {
+                match req {
+                    ClientRequests::Quit => {
+                        info!("Quit requested from the tray");
+                        break;
+                    }
+                    // Opening a folder doesn't touch systemd, so it needs no authorization
+                    ClientRequests::OpenFolder => {
+                        match &drives[index].mount_info.path {
+                            Some(path) => open_folder(&opener, path),
+                            None => warn!("{} has no mount point to open", drives[index].mount_name()),
+                        }
+                        continue;
+                    }
+                    ClientRequests::OpenAbout => {
+                        // The opener may be a file manager, which needn't take URLs
+                        open_folder("xdg-open", env!("CARGO_PKG_REPOSITORY"));
+                        continue;
+                    }
+                    ClientRequests::CopyPath => {
+                        let drive = &drives[index];
+                        let info = &drive.mount_info;
+                        match info.path.clone().or_else(|| info.what.clone()) {
+                            Some(path) => {
+                                tokio::spawn(copy_path(drive.systemd_name.clone(), path, drive.quiet));
+                            }
+                            None => warn!("{} has no path to copy", drive.mount_name()),
+                        }
+                        continue;
+                    }
+                    ClientRequests::SetQuiet(quiet) => {
+                        let drive = &mut drives[index];
+                        drive.quiet = quiet;
+                        drive.update_tray(|t| t.quiet = quiet).await;
+                        prefs.set_quiet(&drive.systemd_name, quiet);
+                        continue;
+                    }
+                    // Goes through the usual change handling, unchanged states are ignored there
+                    ClientRequests::Refresh => {
+                        let units = drives[index].units.clone();
+                        let changes = changes_sender.clone();
+                        tokio::spawn(async move {
+                            match units.states().await {
+                                Ok((mount, automount)) => {
+                                    let _ = changes.send((index, StateChange::Mount(mount)));
+                                    let _ = changes.send((index, StateChange::Automount(automount)));
+                                }
+                                Err(e) => warn!("Could not refresh {}: {e}", units.mount_name),
+                            }
+                            let _ = changes.send((index, StateChange::MountInfo(units.mount_info().await)));
+                            let _ = changes.send((index, StateChange::AutomountAtBoot(units.automount_at_boot().await)));
+                        });
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                let drive = &drives[index];
+                if actions_blocked {
+                    warn!(?req, "Ignoring request for {}, polkit is not available to authorize it", drive.systemd_name);
+                    let name = drive.systemd_name.clone();
+                    let body = tr!("notify-polkit-missing", request = req.label());
+                    tokio::spawn(async move { notify_failure(&name, &body).await });
+                    continue;
+                }
+                if let ClientRequests::MountAll | ClientRequests::DisconnectAll = req {
+                    if batch.is_some() {
+                        warn!(?req, "Ignoring request, the last one for every drive is still running");
+                        continue;
+                    }
+                    let started = Batch::start(&req, &drives);
+                    if started.waiting.is_empty() {
+                        info!(?req, "Ignoring request, no drive needs it");
+                        continue;
+                    }
+                    for &index in &started.waiting {
+                        ready.push_back((index, started.request.clone()));
+                    }
+                    batch = Some(started);
+                    continue;
+                }
+                if let Some((_, current)) = running.values().find(|(busy, _)| *busy == index) {
+                    let queue = queued.entry(index).or_default();
+                    if *current == req || queue.contains(&req) {
+                        debug!(?req, "Ignoring request, the same one for {} is already pending", drive.systemd_name);
+                    } else {
+                        debug!(?req, "Queueing request until the one running for {} finishes", drive.systemd_name);
+                        queue.push_back(req);
+                    }
+                    continue;
+                }
+                if req == ClientRequests::ReloadDaemon {
+                    if last_reload.is_some_and(|at: Instant| at.elapsed() < RELOAD_COOLDOWN) {
+                        warn!("Ignoring request to reload systemd, it was reloaded less than {}s ago", RELOAD_COOLDOWN.as_secs());
+                        continue;
+                    }
+                    last_reload = Some(Instant::now());
+                }
+
+                let context = RequestContext {
+                    authority: bus.authority.clone(),
+                    manager: bus.manager.clone(),
+                    system: bus.system.clone(),
+                    authorizer: authorizer.clone(),
+                    job_timeout,
+                    sync_before_unmount: config.sync_before_unmount,
+                    confirm_disconnect: config.confirm_disconnect,
+                    power_off_on_disconnect: config.power_off_on_disconnect,
+                    mount_retries: config.mount_retries,
+                    inhibit_sleep: config.inhibit_sleep,
+                    verify_automount: config.verify_automount,
+                    // Summed up once the batch is done, failures are still shown
+                    quiet: drive.quiet || batch.as_ref().is_some_and(|b| b.includes(index, &req)),
+                    operations: operations.clone(),
+                    dry_run: config.dry_run,
+                    crypt_device: drive.crypt_device(),
+                    partitions: match req {
+                        ClientRequests::DisconnectDrive => drive
+                            .partitions
+                            .iter()
+                            .map(|&other| drives[other].units.clone())
+                            .collect(),
+                        _ => Vec::new(),
+                    },
+                    requester: sender.clone(),
+                };
+                let task = jobs.spawn(handle_request(
+                    context,
+                    index,
+                    drive.systemd_name.clone(),
+                    drive.units.clone(),
+                    req.clone(),
+                ));
+                running.insert(task.id(), (index, req));
+                drives[index].set_busy(true).await;
+            }
+            Some(finished) = jobs.join_next_with_id() => {
+                let (id, succeeded) = match finished {
+                    Ok((id, succeeded)) => (id, succeeded),
+                    Err(e) => {
+                        warn!("Request task failed: {e}");
+                        (e.id(), None)
+                    }
+                };
+                if let Some((index, req)) = running.remove(&id) {
+                    if batch.as_mut().is_some_and(|b| b.finish(index, &req, succeeded == Some(true))) {
+                        if let Some(done) = batch.take() {
+                            tokio::spawn(notify_summary(done.summary(), done.outcome()));
+                        }
+                    }
+                    if let Some(next) = queued.get_mut(&index).and_then(VecDeque::pop_front) {
+                        ready.push_back((index, next));
+                    }
+                    let enabling_automount = req == ClientRequests::EnableAutomounting;
+                    let drive = &mut drives[index];
+                    drive.set_busy(false).await;
+                    // A remount leaves the unit's state as it was
+                    drive.refresh_read_only();
+                    match succeeded {
+                        Some(true) => drive.back_off_automount(false).await,
+                        Some(false) if enabling_automount => drive.back_off_automount(true).await,
+                        _ => {}
+                    }
+
+                    // Without a subscription systemd doesn't say it has reloaded
+                    if req == ClientRequests::ReloadDaemon && succeeded == Some(true) {
+                        for (index, drive) in drives.iter().enumerate() {
+                            let info = StateChange::MountInfo(drive.units.mount_info().await);
+                            let at_boot = StateChange::AutomountAtBoot(drive.units.automount_at_boot().await);
+                            let _ = changes_sender.send((index, info));
+                            let _ = changes_sender.send((index, at_boot));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if !jobs.is_empty() {
+        info!(
+            "Shutting down, giving running requests {}s to finish",
+            SHUTDOWN_GRACE.as_secs()
+        );
+        let _ = time::timeout(SHUTDOWN_GRACE, async {
+            while jobs.join_next().await.is_some() {}
+        })
+        .await;
+    }
+
+    for drive in &drives {
+        if let Some(handle) = &drive.handle {
+            handle.shutdown().await;
+        }
+    }
+
+    Ok(())
+}
+
+/// `MountAll` or `DisconnectAll`, sent on to each drive it applies to and
+/// reported in one notification once the last of them finished.
+struct Batch {
+    request: ClientRequests,
+    waiting: HashSet<usize>,
+    succeeded: usize,
+    failed: usize,
+}
+
+impl Batch {
+    /// Swaps are left alone, they aren't drives to mount or disconnect.
+    fn start(req: &ClientRequests, drives: &[Drive]) -> Self {
+        let (request, mounted) = match req {
+            ClientRequests::DisconnectAll => (ClientRequests::PrepareDisconnect, true),
+            _ => (ClientRequests::Mount, false),
+        };
+        let waiting = drives
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.units.kind == UnitKind::Mount)
+            .filter(|(_, d)| (d.mount_state == MountState::Mounted) == mounted)
+            .map(|(index, _)| index)
+            .collect();
+        Self {
+            request,
+            waiting,
+            succeeded: 0,
+            failed: 0,
+        }
+    }
+
+    fn includes(&self, index: usize, req: &ClientRequests) -> bool {
+        *req == self.request && self.waiting.contains(&index)
+    }
+
+    /// Counts the drive's request, whether it was the last one outstanding.
+    fn finish(&mut self, index: usize, req: &ClientRequests, succeeded: bool) -> bool {
+        if !self.includes(index, req) {
+            return false;
+        }
+        self.waiting.remove(&index);
+        if succeeded {
+            self.succeeded += 1;
+        } else {
+            self.failed += 1;
+        }
+        self.waiting.is_empty()
+    }
+
+    fn outcome(&self) -> Outcome {
+        if self.failed == 0 {
+            Outcome::Success
+        } else {
+            Outcome::Failure
+        }
+    }
+
+    fn summary(&self) -> String {
+        match self.request {
+            ClientRequests::PrepareDisconnect => tr!(
+                "notify-disconnected-all",
+                disconnected = self.succeeded,
+                failed = self.failed
+            ),
+            _ => tr!(
+                "notify-mounted-all",
+                mounted = self.succeeded,
+                failed = self.failed
+            ),
+        }
+    }
+}
+
+async fn notify_summary(body: String, outcome: Outcome) {
+    let mut notification = notification(&tr!("notify-all-drives"), outcome);
+    notification.body(&body);
+    show(&notification).await;
+}
+
+const ENABLE_AUTOMOUNT_ACTION: &str = "enable-automount";
+const LAZY_UNMOUNT_ACTION: &str = "lazy-unmount";
+const CONFIRM_ACTION: &str = "confirm";
+const CANCEL_ACTION: &str = "cancel";
+
+/// A request that waited for its drive's previous one first, then whatever
+/// arrives on the channel.
+async fn next_request(
+    ready: &mut VecDeque<(usize, ClientRequests)>,
+    events: &mut mpsc::UnboundedReceiver<(usize, ClientRequests)>,
+) -> Option<(usize, ClientRequests)> {
+    match ready.pop_front() {
+        Some(request) => Some(request),
+        None => events.recv().await,
+    }
+}
+
+/// Shows the notification and waits for it to be answered, true only when
+/// `accept` was clicked rather than another action or dismissing it.
+async fn ask(notification: Notification, accept: &'static str) -> bool {
+    let Some(shown) = show(&notification).await else {
+        return false;
+    };
+
+    task::spawn_blocking(move || {
+        let mut confirmed = false;
+        shown.wait_for_action(|action| confirmed = action == accept);
+        confirmed
+    })
+    .await
+    .unwrap_or(false)
+}
+
+async fn ask_to_disconnect(systemd_name: &str) -> bool {
+    let mut notification = Notification::new();
+    notification
+        .summary(&tr!("notify-summary", drive = systemd_name))
+        .icon("drive-harddisk")
+        .body(&tr!("notify-confirm-disconnect"))
+        .action(CONFIRM_ACTION, &tr!("notify-confirm"))
+        .action(CANCEL_ACTION, &tr!("notify-cancel"));
+
+    ask(notification, CONFIRM_ACTION).await
+}
+
+/// Asks before disconnecting a drive with files still open on any of the
+/// mounts, or when that couldn't be checked. True when nothing holds them or
+/// the user chose to go ahead anyway, a stop that then fails on a busy mount
+/// offers the lazy unmount.
+async fn confirm_disconnect(systemd_name: &str, mounts: &[&Units]) -> bool {
+    let mut holders = Vec::new();
+    let mut unverified = false;
+    for units in mounts {
+        let Some(path) = units.mount_info().await.path else {
+            continue;
+        };
+        match holders::of(&path).await {
+            Some(held) if !held.is_empty() => {
+                warn!("{path} is still in use by {}", held.join(", "));
+                holders.extend(held);
+            }
+            Some(_) => {}
+            None => {
+                warn!("Could not tell whether {path} is still in use");
+                unverified = true;
+            }
+        }
+    }
+    holders.sort();
+    holders.dedup();
+    if holders.is_empty() && !unverified {
+        return true;
+    }
+
+    if holders.is_empty() {
+        let mut notification = Notification::new();
+        notification
+            .summary(&tr!("notify-summary", drive = systemd_name))
+            .icon("drive-harddisk")
+            .body(&tr!("notify-unverified-idle"))
+            .action(CONFIRM_ACTION, &tr!("notify-disconnect-anyway"))
+            .action(CANCEL_ACTION, &tr!("notify-cancel"));
+        return ask(notification, CONFIRM_ACTION).await;
+    }
+
+    let mut notification = Notification::new();
+    notification
+        .summary(&tr!("notify-summary", drive = systemd_name))
+        .icon("drive-harddisk")
+        .body(&tr!("notify-in-use", processes = holders.join(", ")))
+        .urgency(Urgency::Critical)
+        .action(CONFIRM_ACTION, &tr!("notify-disconnect-anyway"))
+        .action(CANCEL_ACTION, &tr!("notify-cancel"));
+
+    ask(notification, CONFIRM_ACTION).await
+}
+
+/// What a request task needs, cloned so the main loop keeps handling state
+/// changes while the job runs.
+struct RequestContext {
+    authority: Option<AuthorityProxy<'static>>,
+    manager: ManagerProxy<'static>,
+    system: zbus::Connection,
+    authorizer: Arc<Authorizer>,
+    job_timeout: Duration,
+    sync_before_unmount: bool,
+    confirm_disconnect: bool,
+    power_off_on_disconnect: bool,
+    mount_retries: u32,
+    inhibit_sleep: bool,
+    verify_automount: bool,
+    /// Successes go unannounced, failures are always shown.
+    quiet: bool,
+    operations: Arc<metrics::Operations>,
+    dry_run: bool,
+    /// The LUKS container behind the drive, if any, unlocked before mounting.
+    crypt_device: Option<String>,
+    /// The units of the other mounts `DisconnectDrive` stops.
+    partitions: Vec<Units>,
+    requester: mpsc::UnboundedSender<(usize, ClientRequests)>,
+}
+
+/// Whether the request succeeded, `None` when it was called off before
+/// anything was tried.
+async fn handle_request(
+    context: RequestContext,
+    index: usize,
+    systemd_name: String,
+    units: Units,
+    req: ClientRequests,
+) -> Option<bool> {
+    let RequestContext {
+        authority,
+        manager,
+        system,
+        authorizer,
+        job_timeout,
+        sync_before_unmount,
+        confirm_disconnect: ask_first,
+        power_off_on_disconnect,
+        mount_retries,
+        inhibit_sleep,
+        verify_automount,
+        quiet,
+        operations,
+        dry_run,
+        crypt_device,
+        partitions,
+        requester,
+    } = context;
+    let Units {
+        mount, automount, ..
+    } = &units;
+
+    // Asked for while this drive's task is running, so further clicks are
+    // ignored until the prompt is answered
+    let req = match req {
+        ClientRequests::RequestDisconnect => {
+            if ask_first && !ask_to_disconnect(&systemd_name).await {
+                info!("Disconnecting {systemd_name} was not confirmed");
+                return None;
+            }
+            ClientRequests::PrepareDisconnect
+        }
+        req => req,
+    };
+
+    // The user instance manages the user's own units, no polkit involved
+    let mut result = Ok(true);
+    if dry_run {
+        info!(
+            ?req,
+            "(dry-run) Would check authorization for {systemd_name}"
+        );
+    } else if let Some(authority) = &authority {
+        for action_id in req.polkit_actions(power_off_on_disconnect) {
+            result = authorizer.check(authority, action_id).await;
+            if !matches!(result, Ok(true)) {
+                break;
+            }
+        }
+    }
+
+    let denied = match result {
+        Ok(true) => None,
+        Ok(false) => {
+            warn!(?req, "Not authorized to manage {systemd_name}");
+            Some(tr!("notify-not-authorized", request = req.label()))
+        }
+        Err(e) => {
+            warn!(?req, "Could not check authorization: {e}");
+            Some(tr!(
+                "notify-request-failed",
+                request = req.label(),
+                error = e
+            ))
+        }
+    };
+    if let Some(body) = denied {
+        // Counted like the requests that fail further on
+        operations.record(&systemd_name, req.name(), false);
+        notify_failure(&systemd_name, &body).await;
+        return Some(false);
+    }
+
+    if let ClientRequests::EmergencyDisconnect = req {
+        warn!("EMERGENCY DISCONNECT of {systemd_name}, not checking for open files or syncing");
+    }
+
+    // Every mount the request stops, the other partitions only when disconnecting the drive
+    let stopping: Vec<&Units> = std::iter::once(&units).chain(&partitions).collect();
+
+    if let ClientRequests::PrepareDisconnect | ClientRequests::DisconnectDrive = req {
+        if !confirm_disconnect(&systemd_name, &stopping).await {
+            info!("Disconnecting {systemd_name} was cancelled");
+            return None;
+        }
+    }
+
+    debug!(?req, "Handling request for {systemd_name}");
+
+    // Held until the request is done, so a suspend can't cut a job short
+    let _inhibitor = if inhibit_sleep && !dry_run {
+        sleep::inhibit(&system, "block", &req.label()).await
+    } else {
+        None
+    };
+
+    let actions = Actions {
+        manager: &manager,
+        system: &system,
+        job_timeout,
+        dry_run,
+    };
+    let Units {
+        mount_name,
+        automount_name,
+        ..
+    } = &units;
+    let swap = units.kind == UnitKind::Swap;
+
+    let unmounting = matches!(
+        req,
+        ClientRequests::PrepareDisconnect
+            | ClientRequests::Unmount
+            | ClientRequests::DisconnectDrive
+    );
+    if unmounting && sync_before_unmount {
+        for units in &stopping {
+            // Opening the mount point of an idle automount would mount it just to sync
+            if matches!(units.states().await, Ok((MountState::Mounted, _))) {
+                actions.sync(units.mount_info().await.path).await;
+            }
+        }
+    }
+
+    let outcome: ExResult<String> = async {
+        match req {
+            ClientRequests::PrepareDisconnect => {
+                // Looked up first, the unit may forget its source once stopped
+                let what = units.mount_info().await.what;
+                try_join!(
+                    actions.stop_automount(&units, "replace"),
+                    actions.stop(mount, mount_name)
+                )?;
+
+                let Some(what) = what.filter(|_| power_off_on_disconnect) else {
+                    return Ok(tr!("notify-disconnected"));
+                };
+                Ok(match actions.power_off(&what).await {
+                    Ok(true) => tr!("notify-safe-to-remove"),
+                    Ok(false) => tr!("notify-disconnected"),
+                    Err(e) => {
+                        warn!("Could not power off {systemd_name}: {e}");
+                        tr!("notify-power-off-failed", error = e)
+                    }
+                })
+            }
+            ClientRequests::EnableAutomounting => {
+                let automount = automount.as_ref().ok_or("a swap has no automount")?;
+                actions.start(automount, automount_name).await?;
+                if !verify_automount {
+                    return Ok(tr!("notify-automount-enabled"));
+                }
+                actions.verify_automount(&units).await?;
+                Ok(tr!("notify-automount-verified"))
+            }
+            ClientRequests::DisableAutomounting => {
+                let automount = automount.as_ref().ok_or("a swap has no automount")?;
+                actions.stop(automount, automount_name).await?;
+                Ok(tr!("notify-automount-disabled"))
+            }
+            ClientRequests::SetAutomountAtBoot(enabled) => {
+                if automount.is_none() {
+                    return Err("a swap has no automount".into());
+                }
+                actions.set_enabled(automount_name.clone(), enabled).await?;
+                Ok(if enabled {
+                    tr!("notify-automount-at-boot")
+                } else {
+                    tr!("notify-no-automount-at-boot")
+                })
+            }
+            ClientRequests::Mount | ClientRequests::Unlock => {
+                if let Some(device) = &crypt_device {
+                    if udisks::is_locked(&system, device).await == Some(true) {
+                        actions.unlock(device, &systemd_name).await?;
+                    }
+                }
+
+                // Enclosures can take a few tries to enumerate after being plugged in
+                let mut delay = Duration::from_secs(1);
+                let mut attempt = 0;
+                loop {
+                    attempt += 1;
+                    match actions.start(mount, mount_name).await {
+                        Ok(()) if swap => break Ok(tr!("notify-swap-on")),
+                        Ok(()) if attempt == 1 => break Ok(tr!("notify-mounted")),
+                        Ok(()) => break Ok(tr!("notify-mounted-after-retries", attempts = attempt)),
+                        Err(e) if attempt <= mount_retries => {
+                            warn!(
+                                "Mounting {systemd_name} failed (attempt {attempt}), retrying in {}s: {e}",
+                                delay.as_secs()
+                            );
+                            time::sleep(delay).await;
+                            delay = (delay * 2).min(MAX_BACKOFF);
+                        }
+                        Err(e) => break Err(e),
+                    }
+                }
+            }
+            ClientRequests::Unmount => {
+                actions.stop(mount, mount_name).await?;
+                Ok(if swap {
+                    tr!("notify-swap-off")
+                } else {
+                    tr!("notify-unmounted")
+                })
+            }
+            ClientRequests::Remount => {
+                actions.stop(mount, mount_name).await?;
+                actions
+                    .start(mount, mount_name)
+                    .await
+                    .map_err(|e| tr!("notify-remount-failed", error = e))?;
+                Ok(tr!("notify-remounted"))
+            }
+            ClientRequests::DisconnectDrive => {
+                let what = units.mount_info().await.what;
+                let stops = stopping.iter().map(|units| {
+                    let actions = &actions;
+                    async move {
+                        try_join!(
+                            actions.stop_automount(units, "replace"),
+                            actions.stop(&units.mount, &units.mount_name)
+                        )
+                    }
+                });
+                try_join_all(stops).await?;
+
+                let what = what.ok_or("the mount's source is unknown")?;
+                Ok(match actions.power_off(&what).await? {
+                    true => tr!("notify-safe-to-remove"),
+                    false => tr!("notify-disconnected"),
+                })
+            }
+            ClientRequests::EmergencyDisconnect => {
+                try_join!(
+                    actions.stop_automount(&units, "replace-irreversibly"),
+                    actions.stop_irreversibly(mount, mount_name)
+                )?;
+                Ok(tr!("notify-disconnected"))
+            }
+            ClientRequests::ForceUnmount => {
+                let what = units
+                    .mount_info()
+                    .await
+                    .what
+                    .ok_or("the mount's source is unknown")?;
+                actions.unmount_lazily(&what).await?;
+                Ok(tr!("notify-lazily-unmounted"))
+            }
+            ClientRequests::SetReadOnly(read_only) => {
+                let path = units
+                    .mount_info()
+                    .await
+                    .path
+                    .ok_or("the mount point is unknown")?;
+                actions.remount(&path, read_only).await?;
+                Ok(if read_only {
+                    tr!("notify-read-only")
+                } else {
+                    tr!("notify-read-write")
+                })
+            }
+            ClientRequests::RequestDisconnect => unreachable!("confirmed above"),
+            ClientRequests::ResetFailed => {
+                actions.reset_failed(mount, mount_name).await?;
+                if let Some(automount) = automount {
+                    actions.reset_failed(automount, automount_name).await?;
+                }
+                Ok(tr!("notify-reset-failed"))
+            }
+            ClientRequests::ReloadDaemon => {
+                actions.reload().await?;
+                Ok(tr!("notify-daemon-reloaded"))
+            }
+            ClientRequests::OpenFolder
+            | ClientRequests::CopyPath
+            | ClientRequests::SetQuiet(_)
+            | ClientRequests::Refresh
+            | ClientRequests::MountAll
+            | ClientRequests::DisconnectAll
+            | ClientRequests::OpenAbout
+            | ClientRequests::Quit => {
+                unreachable!("handled by the main loop")
+            }
+        }
+    }
+    .await;
+
+    let succeeded = outcome.is_ok();
+    operations.record(&systemd_name, req.name(), succeeded);
+
+    if succeeded && quiet {
+        return Some(succeeded);
+    }
+    let mut notification = notification(
+        &systemd_name,
+        if succeeded {
+            Outcome::Success
+        } else {
+            Outcome::Failure
+        },
+    );
+
+    let follow_up = follow_up(&req, succeeded);
+
+    match outcome {
+        Ok(body) => notification.body(&body),
+        Err(e) => {
+            warn!(?req, "Request for {systemd_name} failed: {e}");
+            notification.body(&tr!(
+                "notify-request-failed",
+                request = req.label(),
+                error = e
+            ))
+        }
+    };
+
+    if let Some((action, label, _)) = &follow_up {
+        notification.action(action, label);
+    }
+
+    let Some(shown) = show(&notification).await else {
+        return Some(succeeded);
+    };
+
+    if let Some((expected, _, request)) = follow_up {
+        task::spawn_blocking(move || {
+            shown.wait_for_action(|action| {
+                if action == expected {
+                    let _ = requester.send((index, request));
+                }
+            })
+        });
+    }
+    Some(succeeded)
+}
+
+/// Shows the notification, logging instead of failing when there is no
+/// notification server. Notifications are advisory, never worth stopping for.
+/// A single follow-up action for the request's notification, clicking it
+/// queues another request for the drive.
+fn follow_up(
+    req: &ClientRequests,
+    succeeded: bool,
+) -> Option<(&'static str, String, ClientRequests)> {
+    match (req, succeeded) {
+        (ClientRequests::PrepareDisconnect, true) => Some((
+            ENABLE_AUTOMOUNT_ACTION,
+            tr!("notify-enable-automount"),
+            ClientRequests::EnableAutomounting,
+        )),
+        // Also where going ahead with files still open ends up
+        (
+            ClientRequests::PrepareDisconnect
+            | ClientRequests::DisconnectDrive
+            | ClientRequests::EmergencyDisconnect
+            | ClientRequests::Unmount,
+            false,
+        ) => Some((
+            LAZY_UNMOUNT_ACTION,
+            tr!("notify-lazy-unmount"),
+            ClientRequests::ForceUnmount,
+        )),
+        _ => None,
+    }
+}
+
+async fn show(notification: &Notification) -> Option<NotificationHandle> {
+    match notification.show_async().await {
+        Ok(shown) => Some(shown),
+        Err(e) => {
+            warn!("Could not show a notification: {e}");
+            None
+        }
+    }
+}
+
+/// How non-interactive notifications are shown, set once from the config.
+static NOTIFICATIONS: OnceLock<NotificationSettings> = OnceLock::new();
+
+struct NotificationSettings {
+    timeout: Option<Duration>,
+    icon_success: String,
+    icon_error: String,
+    sound: bool,
+}
+
+/// Decides a notification's icon, urgency and sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Success,
+    Failure,
+}
+
+/// A notification about the drive, with the configured timeout and the
+/// outcome's look. Prompts build their own so they don't disappear before
+/// being answered.
+fn notification(systemd_name: &str, outcome: Outcome) -> Notification {
+    let settings = NOTIFICATIONS.get();
+    let (icon, sound) = match (outcome, settings) {
+        (Outcome::Success, Some(settings)) => (settings.icon_success.as_str(), "complete"),
+        (Outcome::Failure, Some(settings)) => (settings.icon_error.as_str(), "dialog-warning"),
+        (Outcome::Success, None) => ("drive-harddisk", "complete"),
+        (Outcome::Failure, None) => ("dialog-warning", "dialog-warning"),
+    };
+
+    let mut notification = Notification::new();
+    notification
+        .summary(&tr!("notify-summary", drive = systemd_name))
+        .icon(icon);
+    if outcome == Outcome::Failure {
+        notification.urgency(Urgency::Critical);
+    }
+    if settings.is_some_and(|settings| settings.sound) {
+        notification.hint(Hint::SoundName(sound.into()));
+    }
+    match settings.and_then(|settings| settings.timeout) {
+        Some(Duration::ZERO) => {
+            notification.timeout(Timeout::Never);
+        }
+        Some(timeout) => {
+            notification.timeout(timeout);
+        }
+        None => {}
+    }
+    notification
+}
+
+/// Tells the user a request went nowhere, for failures outside the job itself.
+async fn notify_failure(systemd_name: &str, body: &str) {
+    let mut notification = notification(systemd_name, Outcome::Failure);
+    notification.body(body);
+
+    show(&notification).await;
+}
+
+/// The systemd and UDisks2 calls requests make, in one place so `--dry-run`
+/// can log them and pretend they succeeded instead.
+struct Actions<'a, B: SystemdBackend = ManagerProxy<'static>> {
+    manager: &'a B,
+    system: &'a zbus::Connection,
+    job_timeout: Duration,
+    dry_run: bool,
+}
+
+impl<B: SystemdBackend + Clone> Actions<'_, B> {
+    async fn start(&self, unit: &B::Unit, name: &str) -> ExResult<()> {
+        if self.dry_run {
+            info!("(dry-run) Would start {name}");
+            return Ok(());
+        }
+        job_wait(
+            self.manager,
+            unit,
+            name,
+            JobRequest::Start,
+            self.job_timeout,
+        )
+        .await
+    }
+
+    async fn stop(&self, unit: &B::Unit, name: &str) -> ExResult<()> {
+        self.stop_with(unit, name, "replace").await
+    }
+
+    /// The stop job can't be replaced by a later start, e.g. an access that
+    /// would trigger the automount again.
+    async fn stop_irreversibly(&self, unit: &B::Unit, name: &str) -> ExResult<()> {
+        self.stop_with(unit, name, "replace-irreversibly").await
+    }
+
+    async fn stop_with(&self, unit: &B::Unit, name: &str, mode: &str) -> ExResult<()> {
+        if self.dry_run {
+            info!("(dry-run) Would stop {name} with mode {mode}");
+            return Ok(());
+        }
+        job_wait(
+            self.manager,
+            unit,
+            name,
+            JobRequest::Stop(mode),
+            self.job_timeout,
+        )
+        .await
+    }
+
+    /// Stops the drive's automount, swaps have none to stop.
+    async fn stop_automount(&self, units: &Units<B>, mode: &str) -> ExResult<()> {
+        match &units.automount {
+            Some(automount) => self.stop_with(automount, &units.automount_name, mode).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Opens the mount point, which the automount answers by mounting the
+    /// drive, and waits for the mount unit to follow. Both are bounded by the
+    /// job timeout, a hanging open is left to its thread.
+    async fn verify_automount(&self, units: &Units<B>) -> ExResult<()> {
+        let path = units
+            .mount_info()
+            .await
+            .path
+            .ok_or("automounting was enabled, but the mount point is unknown")?;
+        if self.dry_run {
+            info!("(dry-run) Would open {path} and wait for it to be mounted");
+            return Ok(());
+        }
+
+        let verified = time::timeout(self.job_timeout, async {
+            let opening = path.clone();
+            // Only opening the directory triggers the automount, a stat doesn't
+            task::spawn_blocking(move || std::fs::read_dir(opening).map(drop))
+                .await?
+                .map_err(|e| {
+                    format!("automounting was enabled, but {path} could not be opened: {e}")
+                })?;
+            loop {
+                match units.states().await?.0 {
+                    MountState::Mounted => return Ok(()),
+                    MountState::Failed => {
+                        return Err(
+                            format!("automounting was enabled, but mounting {path} failed").into(),
+                        )
+                    }
+                    _ => time::sleep(VERIFY_POLL).await,
+                }
+            }
+        })
+        .await;
+        verified.unwrap_or_else(|_| Err(JobError::TimedOut(self.job_timeout).into()))
+    }
+
+    /// Leaves the unit `dead`, a no-op for units that haven't failed.
+    async fn reset_failed(&self, unit: &B::Unit, name: &str) -> ExResult<()> {
+        if self.dry_run {
+            info!("(dry-run) Would reset the failed state of {name}");
+            return Ok(());
+        }
+        self.manager.reset_failed(unit).await?;
+        Ok(())
+    }
+
+    async fn reload(&self) -> ExResult<()> {
+        if self.dry_run {
+            info!("(dry-run) Would reload systemd");
+            return Ok(());
+        }
+        self.manager.reload().await?;
+        Ok(())
+    }
+
+    /// Unit file changes only take effect once the daemon has been reloaded.
+    async fn set_enabled(&self, unit: String, enabled: bool) -> ExResult<()> {
+        if self.dry_run {
+            let verb = if enabled { "enable" } else { "disable" };
+            info!("(dry-run) Would {verb} {unit} and reload systemd");
+            return Ok(());
+        }
+
+        self.manager.set_enabled(&unit, enabled).await?;
+        self.manager.reload().await?;
+        Ok(())
+    }
+
+    /// The passphrase is wiped as soon as UDisks2 has been handed it.
+    async fn unlock(&self, device: &str, systemd_name: &str) -> ExResult<()> {
+        if self.dry_run {
+            info!("(dry-run) Would ask for a passphrase and unlock {device}");
+            return Ok(());
+        }
+
+        let passphrase = passphrase::obtain(systemd_name).await?;
+        udisks::unlock(self.system, device, passphrase.as_str()?).await?;
+        info!("Unlocked {device}");
+        Ok(())
+    }
+
+    async fn unmount_lazily(&self, what: &str) -> ExResult<()> {
+        if self.dry_run {
+            info!("(dry-run) Would lazily unmount {what}");
+            return Ok(());
+        }
+        Ok(udisks::unmount_lazily(self.system, what).await?)
+    }
+
+    async fn remount(&self, path: &str, read_only: bool) -> ExResult<()> {
+        if self.dry_run {
+            let mode = if read_only { "read-only" } else { "read-write" };
+            info!("(dry-run) Would remount {path} {mode}");
+            return Ok(());
+        }
+        Ok(remount::remount(path, read_only).await?)
+    }
+
+    async fn power_off(&self, what: &str) -> zbus::Result<bool> {
+        if self.dry_run {
+            info!("(dry-run) Would power off the drive holding {what}");
+            return Ok(true);
+        }
+        udisks::power_off(self.system, what).await
+    }
+
+    /// Flushes buffered writes to the drive, or to every filesystem when its mount
+    /// point is unknown. Failures are only logged so the unmount still goes ahead.
+    async fn sync(&self, mount_point: Option<String>) {
+        if self.dry_run {
+            let target = mount_point.as_deref().unwrap_or("every filesystem");
+            info!("(dry-run) Would sync {target}");
+            return;
+        }
+
+        let synced = task::spawn_blocking(move || sync_filesystem(mount_point.as_deref())).await;
+
+        match synced {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Could not sync before unmounting: {e}"),
+            Err(e) => warn!("Could not sync before unmounting: {e}"),
+        }
+    }
+}
+
+/// Reads free space of the mounted drives that want it all at once, off the
+/// main loop, and sends each back as a [`StateChange::Space`].
+fn poll_space(
+    drives: &[Drive],
+    changes: &mpsc::UnboundedSender<(usize, StateChange)>,
+) -> JoinHandle<()> {
+    let mounted: Vec<(usize, String)> = drives
+        .iter()
+        .enumerate()
+        .filter(|(_, drive)| drive.mount_state == MountState::Mounted && drive.wants_space())
+        .filter_map(|(index, drive)| Some((index, drive.mount_info.path.clone()?)))
+        .collect();
+    let changes = changes.clone();
+    tokio::spawn(async move {
+        let spaces = join_all(mounted.iter().map(|(_, path)| Space::of(path))).await;
+        for ((index, _), space) in mounted.into_iter().zip(spaces) {
+            if let Some(space) = space {
+                let _ = changes.send((index, StateChange::Space(space)));
+            }
+        }
+    })
+}
+
+/// Blocks until the filesystem at `mount_point`, or every one without it, is flushed.
+fn sync_filesystem(mount_point: Option<&str>) -> std::io::Result<()> {
+    match mount_point {
+        Some(path) => {
+            let dir = std::fs::File::open(path)?;
+            nix::unistd::syncfs(dir.as_raw_fd()).map_err(std::io::Error::from)
+        }
+        None => {
+            nix::unistd::sync();
+            Ok(())
+        }
+    }
+}
+
+/// Syncs the mounted drives, then lets the suspend logind announced go ahead.
+fn sync_before_sleep(mount_points: Vec<String>, delay: Option<sleep::Inhibitor>) {
+    task::spawn_blocking(move || {
+        for path in mount_points {
+            match sync_filesystem(Some(&path)) {
+                Ok(()) => debug!("Synced {path} before sleeping"),
+                Err(e) => warn!("Could not sync {path} before sleeping: {e}"),
+            }
+        }
+        drop(delay);
+    });
+}
+
+/// Runs the opener in the background, it's only waited on to log how it exited.
+fn open_folder(opener: &str, path: &str) {
+    let mut words = opener.split_whitespace();
+    let Some(program) = words.next() else {
+        return;
+    };
+
+    let child = Command::new(program)
+        .args(words)
+        .arg(path)
+        .stdin(Stdio::null())
+        .spawn();
+
+    match child {
+        Ok(mut child) => {
+            let program = program.to_owned();
+            tokio::spawn(async move {
+                match child.wait().await {
+                    Ok(status) if status.success() => debug!("{program} exited"),
+                    Ok(status) => warn!("{program} exited with {status}"),
+                    Err(e) => warn!("Could not wait for {program}: {e}"),
+                }
+            });
+        }
+        Err(e) => warn!("Could not run {program}: {e}"),
+    }
+}
+
+async fn copy_path(systemd_name: String, path: String, quiet: bool) {
+    if let Err(e) = clipboard::copy(&path).await {
+        warn!("Could not copy {path}: {e}");
+        let body = tr!("notify-copy-failed", error = e);
+        notify_failure(&systemd_name, &body).await;
+        return;
+    }
+
+    if quiet {
+        return;
+    }
+    let mut notification = notification(&systemd_name, Outcome::Success);
+    notification
+        .icon("edit-copy")
+        .body(&tr!("notify-copied", path = path.as_str()));
+    show(&notification).await;
+}
+
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+const LOW_SPACE_SLACK: Duration = Duration::from_secs(1);
+const RELOAD_COOLDOWN: Duration = Duration::from_secs(30);
+const HEALTH_REFRESH: Duration = Duration::from_secs(10 * 60);
+/// Enabling automounting waits this long after failing, doubled with every
+/// further failure in a row.
+const AUTOMOUNT_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_AUTOMOUNT_BACKOFF: Duration = Duration::from_secs(300);
+
+struct Shutdown {
+    terminate: Signal,
+    interrupt: Signal,
+}
+
+impl Shutdown {
+    fn listen() -> std::io::Result<Self> {
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    async fn requested(&mut self) {
+        select! {
+            _ = self.terminate.recv() => {}
+            _ = self.interrupt.recv() => {}
+        }
+    }
+}
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+struct Bus {
+    instance: Instance,
+    /// Used instead of the instance's usual bus when given.
+    address: Option<zbus::Address>,
+    conn: zbus::Connection,
+    /// UDisks2 and logind are on the system bus for either instance, this is
+    /// `conn` itself for the system instance at its usual address.
+    system: zbus::Connection,
+    authority: Option<AuthorityProxy<'static>>,
+    /// polkit is neither running nor activatable on the system bus.
+    polkit_missing: bool,
+    manager: ManagerProxy<'static>,
+    /// Set when systemd refused the subscription, sub-states are then polled
+    /// this often instead of arriving as signals.
+    polling: Option<Duration>,
+    poll_interval: Duration,
+}
+
+impl Bus {
+    async fn open(
+        instance: Instance,
+        address: Option<&zbus::Address>,
+    ) -> zbus::Result<zbus::Connection> {
+        match (address, instance) {
+            (Some(address), _) => {
+                zbus::connection::Builder::address(address.clone())?
+                    .build()
+                    .await
+            }
+            (None, Instance::System) => zbus::Connection::system().await,
+            (None, Instance::User) => zbus::Connection::session().await,
+        }
+    }
+
+    async fn connect(
+        instance: Instance,
+        address: Option<zbus::Address>,
+        poll_interval: Duration,
+    ) -> ExResult<Self> {
+        let conn = Self::open(instance, address.as_ref()).await?;
+        let system = match (instance, &address) {
+            (Instance::System, None) => conn.clone(),
+            _ => zbus::Connection::system().await?,
+        };
+        let authority = match instance {
+            Instance::System if auth::polkit_available(&conn).await => {
+                Some(AuthorityProxy::new(&conn).await?)
+            }
+            Instance::System => {
+                warn!("polkit is not available on the system bus");
+                None
+            }
+            Instance::User => None,
+        };
+        let polkit_missing = instance == Instance::System && authority.is_none();
+
+        let manager = ManagerProxy::new(&conn).await?;
+
+        let polling = match SystemdBackend::subscribe(&manager).await {
+            Ok(()) => None,
+            Err(e) => {
+                warn!(
+                    "Could not subscribe to systemd: {e}, polling unit states every {}s instead",
+                    poll_interval.as_secs_f32()
+                );
+                Some(poll_interval)
+            }
+        };
+
+        Ok(Self {
+            instance,
+            address,
+            conn,
+            system,
+            authority,
+            polkit_missing,
+            manager,
+            polling,
+            poll_interval,
+        })
+    }
+
+    async fn connect_with_backoff(
+        instance: Instance,
+        address: Option<zbus::Address>,
+        poll_interval: Duration,
+    ) -> Self {
+        let mut delay = Duration::from_secs(1);
+
+        loop {
+            match Self::connect(instance, address.clone(), poll_interval).await {
+                Ok(bus) => return bus,
+                Err(e) => warn!(
+                    "Could not connect to the {instance} bus: {e}, retrying in {}s",
+                    delay.as_secs()
+                ),
+            }
+
+            time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    async fn reconnect(
+        instance: Instance,
+        address: Option<zbus::Address>,
+        poll_interval: Duration,
+        drives: &mut [Drive],
+        changes: &mpsc::UnboundedSender<(usize, StateChange)>,
+    ) -> Self {
+        let mut delay = Duration::from_secs(1);
+
+        loop {
+            let bus = Self::connect_with_backoff(instance, address.clone(), poll_interval).await;
+
+            let mut attached = Ok(());
+            for (index, drive) in drives.iter_mut().enumerate() {
+                attached = drive.attach(&bus, index, changes.clone()).await;
+                if attached.is_err() {
+                    break;
+                }
+            }
+
+            match attached {
+                Ok(()) => {
+                    info!("Reconnected to the {instance} bus");
+                    return bus;
+                }
+                Err(e) => warn!(
+                    "Could not restore the drives after reconnecting: {e}, retrying in {}s",
+                    delay.as_secs()
+                ),
+            }
+
+            for drive in drives.iter() {
+                drive.watcher.abort();
+            }
+
+            time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+const TRAY_ATTEMPTS: u32 = 5;
+
+/// The theme path and name a drive's `icon` is shown with. Icon files are
+/// found through their directory, as tray hosts look up names there first.
+fn custom_icon(icon: &str) -> Option<(String, String)> {
+    let path = Path::new(icon);
+    if !path.is_absolute() {
+        return Some((String::new(), icon.to_owned()));
+    }
+
+    let supported = path
+        .extension()
+        .is_some_and(|ext| ext == "png" || ext == "svg");
+    if !supported || !path.is_file() {
+        return None;
+    }
+    let dir = path.parent()?.to_str()?;
+    let name = path.file_stem()?.to_str()?;
+    Some((dir.to_owned(), name.to_owned()))
+}
+
+/// A drive's own tray, or its part of the one shared with its partitions.
+#[derive(Clone)]
+enum TrayHandle {
+    Own(Handle<DiskTray>),
+    Clicks(Handle<ClickTray>),
+    Member(Handle<GroupTray>, usize),
+}
+
+impl TrayHandle {
+    async fn update<R>(&self, update: impl FnOnce(&mut DiskTray) -> R) -> Option<R> {
+        match self {
+            Self::Own(handle) => handle.update(update).await,
+            Self::Clicks(handle) => handle.update(|clicks| update(&mut clicks.tray)).await,
+            Self::Member(handle, drive) => handle
+                .update(|group| group.member(*drive).map(update))
+                .await
+                .flatten(),
+        }
+    }
+
+    /// A shared tray goes with the first of its drives.
+    async fn shutdown(&self) {
+        match self {
+            Self::Own(handle) => handle.shutdown().await,
+            Self::Clicks(handle) => handle.shutdown().await,
+            Self::Member(handle, _) => handle.shutdown().await,
+        }
+    }
+}
+
+/// Shows the trays, one per physical drive when `group` is set, and
+/// returns false once the tray host didn't show up.
+async fn show_trays(
+    drives: &mut [Drive],
+    trays: Vec<Option<DiskTray>>,
+    group: bool,
+    on_activate: OnActivate,
+) -> bool {
+    let mut groups: Vec<(Option<OwnedObjectPath>, String, Vec<DiskTray>)> = Vec::new();
+    for tray in trays.into_iter().flatten() {
+        let device = drives[tray.drive].crypt_device();
+        let physical = match device.filter(|_| group) {
+            Some(device) => udisks::drive_of(&drives[tray.drive].system, &device).await,
+            None => None,
+        };
+        let Some((path, name)) = physical else {
+            groups.push((None, String::new(), vec![tray]));
+            continue;
+        };
+        match groups
+            .iter_mut()
+            .find(|(other, _, _)| other.as_ref() == Some(&path))
+        {
+            Some((_, _, members)) => members.push(tray),
+            None => groups.push((Some(path), name, vec![tray])),
+        }
+    }
+
+    for (_, name, mut members) in groups {
+        let indices: Vec<usize> = members.iter().map(|tray| tray.drive).collect();
+        let handles: Vec<TrayHandle> = if members.len() == 1 {
+            let tray = members.remove(0);
+            let handle = match on_activate {
+                OnActivate::Menu => spawn_tray(tray).await.map(TrayHandle::Own),
+                action => spawn_tray(ClickTray { tray, action })
+                    .await
+                    .map(TrayHandle::Clicks),
+            };
+            let Some(handle) = handle else {
+                return false;
+            };
+            vec![handle]
+        } else {
+            info!("Grouping {} mounts of {name} under one icon", members.len());
+            let Some(handle) = spawn_tray(GroupTray { name, members }).await else {
+                return false;
+            };
+            indices
+                .iter()
+                .map(|&index| TrayHandle::Member(handle.clone(), index))
+                .collect()
+        };
+
+        for (&index, handle) in indices.iter().zip(handles) {
+            drives[index].partitions = indices.iter().copied().filter(|&i| i != index).collect();
+            drives[index].show(handle);
+        }
+    }
+
+    true
+}
+
+/// The panel may still be starting during login, so give it a few chances
+/// before running headless.
+async fn spawn_tray<T: ksni::Tray + Clone>(tray: T) -> Option<Handle<T>> {
+    let mut delay = Duration::from_secs(1);
+
+    for attempt in 1..=TRAY_ATTEMPTS {
+        match tray.clone().spawn().await {
+            Ok(handle) => return Some(handle),
+            Err(e) if attempt < TRAY_ATTEMPTS => warn!(
+                "Could not show the tray icon ({attempt}/{TRAY_ATTEMPTS}): {e}, retrying in {}s",
+                delay.as_secs()
+            ),
+            Err(e) => warn!("Could not show the tray icon: {e}, continuing without one"),
+        }
+
+        if attempt < TRAY_ATTEMPTS {
+            time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    None
+}
+
+struct Drive {
+    systemd_name: String,
+    units: Units,
+    /// The bus UDisks2 is asked on, replaced along with `units` on reconnects.
+    system: zbus::Connection,
+    mount_state: MountState,
+    automount_state: AutomountState,
+    mount_info: MountInfo,
+    /// The `What` the displayed identity was looked up from.
+    identity_source: Option<String>,
+    /// Configured LUKS container, the mount's `What` is checked otherwise.
+    encrypted_device: Option<String>,
+    /// Options the unit is expected to mount with, see [`Drive::check_mount_options`].
+    mount_options: Option<String>,
+    auto_unmount_after: Option<Duration>,
+    idle_unmount: Option<JoinHandle<()>>,
+    low_space: Option<LowSpace>,
+    /// When free space was last held against `low_space`.
+    low_space_checked: Option<Instant>,
+    /// Already warned about running low, until it recovers.
+    low_space_warned: bool,
+    /// Follows whether the block device is plugged in, see [`watch_device`].
+    device_watch: Option<JoinHandle<()>>,
+    /// The device the running watch follows.
+    device_source: Option<String>,
+    debounce: Duration,
+    requester: mpsc::UnboundedSender<(usize, ClientRequests)>,
+    /// `None` when running headless.
+    handle: Option<TrayHandle>,
+    /// The other configured mounts on the same physical drive, when grouped.
+    partitions: Vec<usize>,
+    /// Blinks the icon while a request runs.
+    animation: Option<JoinHandle<()>>,
+    /// Counts down in the menu until automounting can be enabled again.
+    automount_cooldown: Option<JoinHandle<()>>,
+    /// Reads SMART data while the device is plugged in.
+    health_watch: Option<JoinHandle<()>>,
+    quiet: bool,
+    watcher: JoinHandle<()>,
+}
+
+impl Drive {
+    fn mount_name(&self) -> String {
+        self.units.mount_name.clone()
+    }
+
+    fn automount_name(&self) -> String {
+        format!("{}.automount", self.systemd_name)
+    }
+
+    fn status(&self) -> control::Status {
+        control::Status {
+            mount: self.mount_state.clone(),
+            automount: self.automount_state.clone(),
+            path: self.mount_info.path.clone(),
+        }
+    }
+
+    async fn update_tray(&self, update: impl FnOnce(&mut DiskTray)) {
+        if let Some(handle) = &self.handle {
+            handle.update(update).await;
+        }
+    }
+
+    async fn set_busy(&mut self, busy: bool) {
+        if let Some(animation) = self.animation.take() {
+            animation.abort();
+        }
+        self.update_tray(|t| {
+            t.busy = busy;
+            t.blink = false;
+        })
+        .await;
+
+        let Some(handle) = self.handle.clone().filter(|_| busy) else {
+            return;
+        };
+        self.animation = Some(tokio::spawn(async move {
+            let mut frames = time::interval(BLINK_INTERVAL);
+            frames.tick().await;
+            loop {
+                frames.tick().await;
+                if handle.update(|t| t.blink = !t.blink).await.is_none() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    /// Counts a failure to enable automounting, or clears the count after a
+    /// request that succeeded. While the cooldown lasts the menu item is
+    /// disabled and ticks down once a second.
+    async fn back_off_automount(&mut self, failed: bool) {
+        if let Some(cooldown) = self.automount_cooldown.take() {
+            cooldown.abort();
+        }
+
+        let Some(handle) = self.handle.clone() else {
+            return;
+        };
+        let now = Instant::now();
+        let failures = handle
+            .update(|t| {
+                if failed {
+                    t.automount_failures += 1;
+                    t.automount_failed_at = Some(now);
+                } else {
+                    t.automount_failures = 0;
+                    t.automount_failed_at = None;
+                }
+                t.automount_failures
+            })
+            .await;
+        let Some(failures) = failures.filter(|_| failed) else {
+            return;
+        };
+
+        let cooldown = automount_backoff(failures);
+        info!(unit = %self.automount_name(), "Not enabling again for {}s", cooldown.as_secs());
+        self.automount_cooldown = Some(tokio::spawn(async move {
+            let mut ticks = time::interval(Duration::from_secs(1));
+            ticks.tick().await;
+            while now.elapsed() < cooldown {
+                ticks.tick().await;
+                // Rebuilds the menu with the time that is left
+                if handle.update(|_| ()).await.is_none() {
+                    break;
+                }
+            }
+        }));
+    }
+
+    async fn refresh_space(&self) {
+        if self.handle.is_none() {
+            return;
+        }
+
+        let space = match &self.mount_info.path {
+            Some(path) => Space::of(path).await,
+            None => None,
+        };
+
+        self.update_tray(|t| t.space = space).await;
+    }
+
+    /// Looks up the backing device in the background whenever the source changed.
+    fn refresh_identity(&mut self) {
+        let Some(handle) = self.handle.clone() else {
+            return;
+        };
+        if self.identity_source == self.mount_info.what {
+            return;
+        }
+        self.identity_source = self.mount_info.what.clone();
+
+        let what = self.identity_source.clone();
+        let system = self.system.clone();
+        tokio::spawn(async move {
+            let identity = match what {
+                Some(what) => Some(Identity::lookup(&system, &what).await),
+                None => None,
+            };
+            handle.update(|t| t.identity = identity).await;
+        });
+    }
+
+    fn crypt_device(&self) -> Option<String> {
+        self.encrypted_device
+            .clone()
+            .or_else(|| self.mount_info.what.clone())
+    }
+
+    /// Asks UDisks2 whether the drive still needs unlocking, in the background.
+    fn refresh_lock(&self) {
+        let (Some(handle), Some(device)) = (self.handle.clone(), self.crypt_device()) else {
+            return;
+        };
+        let system = self.system.clone();
+        tokio::spawn(async move {
+            let locked = udisks::is_locked(&system, &device).await == Some(true);
+            handle.update(|t| t.locked = locked).await;
+        });
+    }
+
+    /// Reads whether the mount is read-only from the mount table, in the background.
+    fn refresh_read_only(&self) {
+        let Some(handle) = self.handle.clone() else {
+            return;
+        };
+        let path = self
+            .mount_info
+            .path
+            .clone()
+            .filter(|_| self.mount_state == MountState::Mounted);
+        tokio::spawn(async move {
+            let read_only = match path {
+                Some(path) => remount::read_only(&path).await,
+                None => None,
+            };
+            handle.update(|t| t.read_only = read_only).await;
+        });
+    }
+
+    /// Restarts the idle countdown while mounted and cancels it otherwise,
+    /// there is at most one per drive.
+    fn update_idle_unmount(&mut self, index: usize) {
+        if let Some(countdown) = self.idle_unmount.take() {
+            countdown.abort();
+        }
+
+        let (Some(after), Some(what)) = (self.auto_unmount_after, &self.mount_info.what) else {
+            return;
+        };
+        if self.mount_state != MountState::Mounted {
+            return;
+        }
+
+        let what = what.clone();
+        let unit = self.mount_name();
+        let requester = self.requester.clone();
+        self.idle_unmount = Some(tokio::spawn(async move {
+            match idle::wait(&what, after).await {
+                Ok(()) => {
+                    info!(unit, "Idle for {}s, unmounting", after.as_secs());
+                    let _ = requester.send((index, ClientRequests::Unmount));
+                }
+                Err(e) => warn!(unit, "Not unmounting when idle: {e}"),
+            }
+        }));
+    }
+
+    /// Warns about configured `mount_options` the unit doesn't have. Changing
+    /// them is left to fstab or the unit file, systemd won't for a single mount.
+    fn check_mount_options(&self) {
+        let Some(expected) = &self.mount_options else {
+            return;
+        };
+        let actual = self.mount_info.options.as_deref().unwrap_or_default();
+        let missing: Vec<&str> = expected
+            .split(',')
+            .filter(|option| !option.is_empty() && !actual.split(',').any(|o| o == *option))
+            .collect();
+        if !missing.is_empty() {
+            warn!(
+                unit = %self.mount_name(),
+                "Not mounted with {}, its options are \"{actual}\"",
+                missing.join(",")
+            );
+        }
+    }
+
+    /// Checks free space right at the next poll and warns again, after
+    /// mounting or the mount point moving.
+    fn reset_low_space(&mut self) {
+        self.low_space_checked = None;
+        self.low_space_warned = false;
+    }
+
+    /// Whether the next space poll should read this drive, for its menu or
+    /// because a low space check is due.
+    fn wants_space(&self) -> bool {
+        self.handle.is_some() || self.low_space_due()
+    }
+
+    /// Once `low_space_interval` passed since the last check, a little early
+    /// as the shared timer won't land on it exactly.
+    fn low_space_due(&self) -> bool {
+        self.low_space.is_some_and(|low_space| {
+            self.low_space_checked
+                .is_none_or(|at| at.elapsed() + LOW_SPACE_SLACK >= low_space.interval)
+        })
+    }
+
+    /// Warns once when a poll finds free space below `low_space_percent`.
+    fn check_low_space(&mut self, space: Space) {
+        let Some(low_space) = self.low_space else {
+            return;
+        };
+        if self.mount_state != MountState::Mounted || space.total == 0 || !self.low_space_due() {
+            return;
+        }
+        self.low_space_checked = Some(Instant::now());
+
+        let low = space.free * 100 < space.total * u64::from(low_space.percent);
+        if low && !self.low_space_warned {
+            warn!(unit = %self.mount_name(), "Only {} free", space::Bytes(space.free));
+            let body = tr!(
+                "notify-low-space",
+                path = self.mount_info.path.as_deref().unwrap_or_default(),
+                free = space::Bytes(space.free).to_string()
+            );
+            let name = self.systemd_name.clone();
+            tokio::spawn(async move { notify_failure(&name, &body).await });
+        }
+        // Recovering re-arms the warning for the next time it runs low
+        self.low_space_warned = low;
+    }
+
+    /// Follows the device the drive is mounted from, or its LUKS container,
+    /// until it changes. Sources that aren't block devices aren't followed and
+    /// count as always plugged in.
+    fn update_device_watch(
+        &mut self,
+        bus: &Bus,
+        index: usize,
+        changes: &mpsc::UnboundedSender<(usize, StateChange)>,
+    ) {
+        let source = self
+            .crypt_device()
+            .map(|what| unit_name::device_path(&what));
+        // Sources that aren't followed have nothing running to check on
+        let running = self.device_watch.as_ref().is_none_or(|w| !w.is_finished());
+        if running && source == self.device_source {
+            return;
+        }
+        if let Some(watch) = self.device_watch.take() {
+            watch.abort();
+        }
+        self.device_source = source.clone();
+
+        // Mapped devices only exist while unlocked, they can't tell whether the drive is there
+        let Some(device) =
+            source.filter(|s| s.starts_with("/dev/") && !s.starts_with("/dev/mapper/"))
+        else {
+            let _ = changes.send((index, StateChange::Present(true)));
+            return;
+        };
+        self.device_watch = Some(tokio::spawn(watch_device(
+            index,
+            bus.manager.clone(),
+            device,
+            bus.polling,
+            changes.clone(),
+        )));
+    }
+
+    /// Reads SMART data every so often while the device is plugged in, with a
+    /// notification when the drive starts failing.
+    async fn update_health_watch(&mut self, present: bool) {
+        if let Some(watch) = self.health_watch.take() {
+            watch.abort();
+        }
+        self.update_tray(|t| t.health = None).await;
+
+        let Some(device) = self.crypt_device().filter(|_| present) else {
+            return;
+        };
+        let handle = self.handle.clone();
+        let systemd_name = self.systemd_name.clone();
+        let unit = self.mount_name();
+        let system = self.system.clone();
+        self.health_watch = Some(tokio::spawn(async move {
+            let mut checks = time::interval(HEALTH_REFRESH);
+            let mut failing = false;
+            loop {
+                checks.tick().await;
+                let health = udisks::health(&system, &device).await;
+
+                let now_failing = health.is_some_and(|health| health.failing);
+                if now_failing && !failing {
+                    warn!(unit, "SMART reports the drive is failing");
+                    notify_failure(&systemd_name, &tr!("notify-health-failing")).await;
+                }
+                failing = now_failing;
+
+                if let Some(handle) = &handle {
+                    handle.update(|t| t.health = health).await;
+                }
+            }
+        }));
+    }
+
+    /// Starts following the drive's units. Its tray, built when `icons` are
+    /// given, is returned for the caller to show, see [`Drive::show`].
+    async fn connect(
+        bus: &Bus,
+        spec: DriveSpec,
+        icons: Option<Icons>,
+        index: usize,
+        requester: mpsc::UnboundedSender<(usize, ClientRequests)>,
+        changes: mpsc::UnboundedSender<(usize, StateChange)>,
+    ) -> ExResult<(Self, Option<DiskTray>)> {
+        let DriveSpec {
+            systemd_name,
+            kind,
+            display_name,
+            auto_unmount_after,
+            debounce,
+            encrypted_device,
+            low_space,
+            icon,
+            mount_options,
+            ..
+        } = spec;
+
+        let units = Units::resolve(&bus.manager, bus.instance, &systemd_name, kind).await?;
+        let display_name = match display_name {
+            Some(name) => name,
+            None => units
+                .description()
+                .await
+                .unwrap_or_else(|| systemd_name.clone()),
+        };
+        let (mount_state, automount_state) = units.states().await?;
+        let mount_info = units.mount_info().await;
+        let automount_at_boot = units.automount_at_boot().await;
+
+        let (icon_theme_path, icons) = match (icons, icon) {
+            (Some(mut icons), Some(icon)) => match custom_icon(&icon) {
+                Some((theme_path, name)) => {
+                    icons.apply(&name);
+                    (theme_path, Some(icons))
+                }
+                None => {
+                    warn!("Could not load the icon {icon} for {systemd_name}, using the default");
+                    (String::new(), Some(icons))
+                }
+            },
+            (icons, _) => (String::new(), icons),
+        };
+        let tray = icons.map(|icons| DiskTray {
+            icon_theme_path,
+            systemd_name: systemd_name.clone(),
+            display_name,
+            drive: index,
+            kind,
+            mount: mount_state.clone(),
+            mount_substate: mount_state.as_str().into(),
+            automount: automount_state.clone(),
+            mount_info: mount_info.clone(),
+            automount_at_boot,
+            identity: None,
+            space: None,
+            health: None,
+            icons,
+            bus_connected: true,
+            polkit_missing: false,
+            pending_job: None,
+            busy: false,
+            blink: false,
+            locked: false,
+            read_only: None,
+            last_mounted: None,
+            last_unmounted: None,
+            present: true,
+            quiet: false,
+            automount_failures: 0,
+            automount_failed_at: None,
+            requester: requester.clone(),
+        });
+
+        let watcher = tokio::spawn(watch_states(
+            index,
+            units.clone(),
+            debounce,
+            bus.polling,
+            changes.clone(),
+        ));
+
+        let mut drive = Self {
+            systemd_name,
+            units,
+            system: bus.system.clone(),
+            mount_state,
+            automount_state,
+            mount_info,
+            identity_source: None,
+            encrypted_device,
+            mount_options,
+            auto_unmount_after,
+            idle_unmount: None,
+            low_space,
+            low_space_checked: None,
+            low_space_warned: false,
+            device_watch: None,
+            device_source: None,
+            animation: None,
+            automount_cooldown: None,
+            health_watch: None,
+            quiet: false,
+            debounce,
+            requester,
+            handle: None,
+            partitions: Vec::new(),
+            watcher,
+        };
+        drive.update_idle_unmount(index);
+        drive.reset_low_space();
+        drive.update_device_watch(bus, index, &changes);
+        drive.check_mount_options();
+
+        Ok((drive, tray))
+    }
+
+    /// Attaches the shown tray and fills in what is looked up in the background.
+    fn show(&mut self, handle: TrayHandle) {
+        self.handle = Some(handle);
+        self.refresh_identity();
+        self.refresh_lock();
+        self.refresh_read_only();
+    }
+
+    async fn attach(
+        &mut self,
+        bus: &Bus,
+        index: usize,
+        changes: mpsc::UnboundedSender<(usize, StateChange)>,
+    ) -> ExResult<()> {
+        let units = Units::resolve(
+            &bus.manager,
+            bus.instance,
+            &self.systemd_name,
+            self.units.kind,
+        )
+        .await?;
+
+        self.watcher = tokio::spawn(watch_states(
+            index,
+            units.clone(),
+            self.debounce,
+            bus.polling,
+            changes.clone(),
+        ));
+
+        (self.mount_state, self.automount_state) = units.states().await?;
+        self.mount_info = units.mount_info().await;
+        self.units = units;
+        self.system = bus.system.clone();
+
+        let automount_at_boot = self.units.automount_at_boot().await;
+        let (mount_state, automount_state, mount_info) = (
+            self.mount_state.clone(),
+            self.automount_state.clone(),
+            self.mount_info.clone(),
+        );
+        self.update_tray(|t| {
+            t.mount = mount_state;
+            t.automount = automount_state;
+            t.mount_info = mount_info;
+            t.automount_at_boot = automount_at_boot;
+            t.pending_job = None;
+            t.bus_connected = true;
+        })
+        .await;
+        self.refresh_identity();
+        self.refresh_read_only();
+        self.update_idle_unmount(index);
+        self.reset_low_space();
+        // Followed on the previous connection, which is gone
+        if let Some(watch) = self.device_watch.take() {
+            watch.abort();
+        }
+        self.device_source = None;
+        self.update_device_watch(bus, index, &changes);
+
+        Ok(())
+    }
+}
+
+/// The drive's units, on the real systemd unless a test hands in another backend.
+#[derive(Clone)]
+struct Units<B: SystemdBackend = ManagerProxy<'static>> {
+    /// The mount unit, or the swap unit for a swap.
+    mount: B::Unit,
+    /// Swaps have none.
+    automount: Option<B::Unit>,
+    manager: B,
+    kind: UnitKind,
+    mount_name: String,
+    automount_name: String,
+}
+
+impl<B: SystemdBackend + Clone> Units<B> {
+    /// The drive's units that systemd has no unit file or fstab entry for.
+    async fn missing(manager: &B, systemd_name: &str, kind: UnitKind) -> zbus::Result<Vec<String>> {
+        let mut units = vec![format!("{systemd_name}.{}", kind.suffix())];
+        if kind == UnitKind::Mount {
+            units.push(format!("{systemd_name}.automount"));
+        }
+
+        let mut missing = Vec::new();
+        for unit in units {
+            let loaded = manager.load_unit(&unit).await?;
+            if manager.load_state(&loaded).await? == "not-found" {
+                missing.push(unit);
+            }
+        }
+
+        Ok(missing)
+    }
+
+    async fn resolve(
+        manager: &B,
+        instance: Instance,
+        systemd_name: &str,
+        kind: UnitKind,
+    ) -> ExResult<Self> {
+        let get_unit = |unit: String| async move {
+            manager.get_unit(&unit).await.map_err(|e| {
+                format!("{unit} is not loaded in the {instance} systemd instance: {e}")
+            })
+        };
+
+        let mount_name = format!("{systemd_name}.{}", kind.suffix());
+        let automount_name = format!("{systemd_name}.automount");
+        let mount = get_unit(mount_name.clone()).await?;
+        let automount = match kind {
+            UnitKind::Mount => Some(get_unit(automount_name.clone()).await?),
+            UnitKind::Swap => None,
+        };
+
+        Ok(Self {
+            mount,
+            automount,
+            manager: manager.clone(),
+            kind,
+            mount_name,
+            automount_name,
+        })
+    }
+
+    /// A swap's automount is always `dead`.
+    async fn states(&self) -> zbus::Result<(MountState, AutomountState)> {
+        let automount = match &self.automount {
+            Some(automount) => {
+                AutomountState::from_substates(&self.manager.sub_state(automount).await?)
+            }
+            None => AutomountState::Dead,
+        };
+        Ok((
+            MountState::from_substates(&self.manager.sub_state(&self.mount).await?),
+            automount,
+        ))
+    }
+
+    /// Read from the unit, so the type is the configured one while unmounted
+    /// and missing when the unit leaves it to autodetection. Swaps only have a `What`.
+    async fn mount_info(&self) -> MountInfo {
+        self.manager.mount_info(&self.mount, self.kind).await
+    }
+
+    /// The mount unit's `Description`, fstab generated units use the mount point.
+    async fn description(&self) -> Option<String> {
+        self.manager
+            .description(&self.mount)
+            .await
+            .ok()
+            .filter(|description| !description.trim().is_empty())
+    }
+
+    /// Read at startup and again whenever the daemon reloads, which enabling
+    /// or disabling the unit file always ends with. `static`, `generated` and
+    /// the like can't be toggled.
+    async fn automount_at_boot(&self) -> Option<bool> {
+        self.automount.as_ref()?;
+        let state = self.manager.unit_file_state(&self.automount_name).await;
+        match state.ok()?.as_str() {
+            "enabled" => Some(true),
+            "disabled" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct MountInfo {
+    path: Option<String>,
+    fs_type: Option<String>,
+    /// As configured while unmounted, and the ones in effect once mounted.
+    options: Option<String>,
+    what: Option<String>,
+}
+
+#[derive(Debug)]
+enum StateChange {
+    Mount(MountState),
+    /// The mount unit's raw sub-state as it arrives, ahead of the debounced
+    /// `Mount` and telling apart the ones [`MountState`] folds together.
+    MountSubState(String),
+    Automount(AutomountState),
+    /// The most recent of the drive's queued systemd jobs, `None` once all have finished.
+    Job(Option<PendingJob>),
+    MountInfo(MountInfo),
+    AutomountAtBoot(Option<bool>),
+    /// Free space read by the shared poll, only sent while mounted.
+    Space(Space),
+    /// Whether the drive's device is plugged in.
+    Present(bool),
+    BusLost,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PendingJob {
+    path: OwnedObjectPath,
+    kind: JobKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobKind {
+    Mounting,
+    Unmounting,
+    EnablingAutomount,
+    DisablingAutomount,
+}
+
+impl std::fmt::Display for JobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Mounting => tr!("job-mounting"),
+            Self::Unmounting => tr!("job-unmounting"),
+            Self::EnablingAutomount => tr!("job-enabling-automount"),
+            Self::DisablingAutomount => tr!("job-disabling-automount"),
+        };
+        f.write_str(&label)
+    }
+}
+
+/// Follows the drive's units and jobs until the bus is lost, which is reported
+/// as [`StateChange::BusLost`], or nobody listens anymore.
+async fn watch_states<B: SystemdBackend + Clone>(
+    drive: usize,
+    units: Units<B>,
+    debounce: Duration,
+    polling: Option<Duration>,
+    changes: mpsc::UnboundedSender<(usize, StateChange)>,
+) {
+    let sub_states = watch_sub_states(
+        drive,
+        &units.manager,
+        (units.mount.clone(), &units.mount_name),
+        units
+            .automount
+            .clone()
+            .map(|automount| (automount, units.automount_name.as_str())),
+        debounce,
+        polling,
+        &changes,
+    );
+    let lost = select! {
+        lost = sub_states => lost,
+        lost = watch_jobs(drive, &units, &changes) => lost,
+    };
+    if lost {
+        let _ = changes.send((drive, StateChange::BusLost));
+    }
+}
+
+/// Sub-state changes are held back until the unit has been quiet for `debounce`,
+/// so a burst like `dead` → `mounting` → `mounted` only reports where it settled.
+/// Returns whether it stopped because the bus was lost, rather than `changes` closing.
+async fn watch_sub_states<B: SystemdBackend>(
+    drive: usize,
+    backend: &B,
+    (mount, mount_name): (B::Unit, &str),
+    automount: Option<(B::Unit, &str)>,
+    debounce: Duration,
+    polling: Option<Duration>,
+    changes: &mpsc::UnboundedSender<(usize, StateChange)>,
+) -> bool {
+    let mut mount_state_change = backend.receive_sub_state_changed(&mount).await;
+    // Empty for a swap, which has no automount
+    let automount_state_change = match &automount {
+        Some((automount, _)) => Some(backend.receive_sub_state_changed(automount).await),
+        None => None,
+    };
+    let mut automount_state_change = futures::stream::iter(automount_state_change).flatten();
+
+    let mut pending_mount = None;
+    let mut pending_automount = None;
+    let settled = time::sleep(debounce);
+    tokio::pin!(settled);
+
+    // Without a subscription the sub-states are read on a timer, and fed
+    // through the same debounce as the signals would have been
+    let mut poll = time::interval(polling.unwrap_or(Duration::MAX));
+    poll.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut polled = None;
+    let mut resubscribe_delay = Duration::from_secs(1);
+
+    loop {
+        let pending = pending_mount.is_some() || pending_automount.is_some();
+
+        select! {
+            s = mount_state_change.next() => {
+                let s = match s {
+                    Some(s) => {
+                        resubscribe_delay = Duration::from_secs(1);
+                        s.ok()
+                    }
+                    None => {
+                        resubscribe(mount_name, &mut resubscribe_delay).await;
+                        mount_state_change = backend.receive_sub_state_changed(&mount).await;
+                        backend.sub_state(&mount).await.ok()
+                    }
+                };
+                let Some(s) = s else { return true };
+                pending_mount = Some(MountState::from_substates(&s));
+                if changes.send((drive, StateChange::MountSubState(s))).is_err() {
+                    return false;
+                }
+                settled.as_mut().reset(time::Instant::now() + debounce);
+            }
+            s = automount_state_change.next(), if automount.is_some() => {
+                let s = match (s, &automount) {
+                    (Some(s), _) => {
+                        resubscribe_delay = Duration::from_secs(1);
+                        s.ok()
+                    }
+                    (None, Some((automount, name))) => {
+                        resubscribe(name, &mut resubscribe_delay).await;
+                        let stream = backend.receive_sub_state_changed(automount).await;
+                        automount_state_change = futures::stream::iter(Some(stream)).flatten();
+                        backend.sub_state(automount).await.ok()
+                    }
+                    (None, None) => continue,
+                };
+                let Some(s) = s else { return true };
+                pending_automount = Some(AutomountState::from_substates(&s));
+                settled.as_mut().reset(time::Instant::now() + debounce);
+            }
+            _ = poll.tick(), if polling.is_some() => {
+                let states = async {
+                    let automount = match &automount {
+                        Some((automount, _)) => {
+                            AutomountState::from_substates(&backend.sub_state(automount).await?)
+                        }
+                        None => AutomountState::Dead,
+                    };
+                    let mount = MountState::from_substates(&backend.sub_state(&mount).await?);
+                    zbus::Result::Ok((mount, automount))
+                };
+                let Ok(states) = states.await else { return true };
+                if polled.as_ref() == Some(&states) {
+                    continue;
+                }
+                // The first poll only records where things stand, the caller already knows
+                if polled.is_some() {
+                    if polled.as_ref().map(|(mount, _)| mount) != Some(&states.0) {
+                        pending_mount = Some(states.0.clone());
+                        let raw = StateChange::MountSubState(states.0.as_str().into());
+                        if changes.send((drive, raw)).is_err() {
+                            return false;
+                        }
+                    }
+                    if polled.as_ref().map(|(_, automount)| automount) != Some(&states.1) {
+                        pending_automount = Some(states.1.clone());
+                    }
+                    settled.as_mut().reset(time::Instant::now() + debounce);
+                }
+                polled = Some(states);
+            }
+            _ = &mut settled, if pending => {
+                let settled = [
+                    pending_mount.take().map(StateChange::Mount),
+                    pending_automount.take().map(StateChange::Automount),
+                ];
+                for change in settled.into_iter().flatten() {
+                    if changes.send((drive, change)).is_err() {
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The drive's queued jobs, and its unit files after a daemon reload. Returns
+/// like [`watch_sub_states`].
+async fn watch_jobs<B: SystemdBackend + Clone>(
+    drive: usize,
+    units: &Units<B>,
+    changes: &mpsc::UnboundedSender<(usize, StateChange)>,
+) -> bool {
+    let manager = &units.manager;
+    let automount_id = async {
+        match &units.automount {
+            Some(automount) => manager.unit_id(automount).await.map(Some),
+            None => Ok(None),
+        }
+    };
+    let streams = try_join!(
+        manager.receive_reloading(),
+        manager.receive_job_new(),
+        manager.receive_job_removed(),
+        manager.unit_id(&units.mount),
+        automount_id,
+    );
+    let Ok((mut reloads, mut job_news, mut job_removals, mount_unit, automount_unit)) = streams
+    else {
+        return true;
+    };
+    let mut jobs: Vec<PendingJob> = Vec::new();
+
+    loop {
+        let change = select! {
+            Some(new) = job_news.next() => {
+                let Ok(new) = new else { continue };
+                let automount = if new.unit == mount_unit {
+                    false
+                } else if automount_unit.as_ref() == Some(&new.unit) {
+                    true
+                } else {
+                    continue;
+                };
+
+                // A job that finished before its type could be read has nothing left to show
+                let Ok(job_type) = manager.job_type(&new.job).await else { continue };
+
+                let kind = match (automount, job_type == "stop") {
+                    (false, false) => JobKind::Mounting,
+                    (false, true) => JobKind::Unmounting,
+                    (true, false) => JobKind::EnablingAutomount,
+                    (true, true) => JobKind::DisablingAutomount,
+                };
+                jobs.push(PendingJob { path: new.job, kind });
+                StateChange::Job(jobs.last().cloned())
+            }
+            Some(removed) = job_removals.next() => {
+                let Ok(removed) = removed else { continue };
+                let before = jobs.len();
+                jobs.retain(|job| job.path != removed.job);
+                if jobs.len() == before {
+                    continue;
+                }
+                StateChange::Job(jobs.last().cloned())
+            }
+            Some(reload) = reloads.next() => {
+                // Unit files may have changed once the daemon has finished reloading
+                match reload {
+                    Ok(false) => {
+                        let info = StateChange::MountInfo(units.mount_info().await);
+                        if changes.send((drive, info)).is_err() {
+                            return false;
+                        }
+                        StateChange::AutomountAtBoot(units.automount_at_boot().await)
+                    }
+                    _ => continue,
+                }
+            }
+            else => return true,
+        };
+
+        if changes.send((drive, change)).is_err() {
+            return false;
+        }
+    }
+}
+
+/// Waits before subscribing again after the unit's sub-state stream ended,
+/// twice as long each time. The caller then reads the sub-state it may have
+/// missed meanwhile.
+async fn resubscribe(unit: &str, delay: &mut Duration) {
+    warn!(
+        "Sub-state changes of {unit} stopped arriving, subscribing again in {}s",
+        delay.as_secs()
+    );
+    time::sleep(*delay).await;
+    *delay = (*delay * 2).min(MAX_BACKOFF);
+}
+
+/// Reports whether the `.device` unit systemd keeps for `device` is plugged
+/// in, as it changes. Absent devices still load, as an inactive unit.
+async fn watch_device<B: SystemdBackend>(
+    drive: usize,
+    manager: B,
+    device: String,
+    polling: Option<Duration>,
+    changes: mpsc::UnboundedSender<(usize, StateChange)>,
+) {
+    let name = format!("{}.device", unit_name::from_mount_point(&device));
+    let unit = match manager.load_unit(&name).await {
+        Ok(unit) => unit,
+        Err(e) => {
+            warn!("Could not follow {name}, showing the drive as plugged in: {e}");
+            return;
+        }
+    };
+
+    let mut state_changes = manager.receive_sub_state_changed(&unit).await;
+    let mut poll = time::interval(polling.unwrap_or(Duration::MAX));
+    poll.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    let mut present = None;
+
+    loop {
+        // Losing the bus is reported by the unit watcher
+        let Ok(state) = manager.sub_state(&unit).await else {
+            return;
+        };
+        let plugged = state == "plugged";
+        if present != Some(plugged) {
+            present = Some(plugged);
+            if changes
+                .send((drive, StateChange::Present(plugged)))
+                .is_err()
+            {
+                return;
+            }
+        }
+
+        select! {
+            Some(_) = state_changes.next() => {}
+            _ = poll.tick(), if polling.is_some() => {}
+            else => return,
+        }
+    }
+}
+
+/// What a job asks systemd to do with its unit, see [`job_wait`].
+#[derive(Debug, Clone, Copy)]
+enum JobRequest<'a> {
+    Start,
+    /// With the job mode, e.g. `replace-irreversibly`.
+    Stop(&'a str),
+}
+
+/// Queues the job and waits for systemd to remove it, failing unless its
+/// result is `done`. A job that left the queue without its `JobRemoved`
+/// being seen is judged by where the unit ended up instead.
+async fn job_wait<B: SystemdBackend>(
+    backend: &B,
+    unit: &B::Unit,
+    name: &str,
+    request: JobRequest<'_>,
+    timeout: Duration,
+) -> ExResult<()> {
+    // Subscribed first, the job can be removed before its path is returned
+    let mut removed_stream = backend.receive_job_removed().await?;
+
+    let job = match request {
+        JobRequest::Start => backend.start(unit, "replace").await?,
+        JobRequest::Stop(mode) => backend.stop(unit, mode).await?,
+    };
+
+    let removed = async {
+        // Also asks whether the job is still queued, it may be gone before
+        // the first signal is read, or never announced while polling
+        let mut check = time::interval(JOB_CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                removed = removed_stream.next() => {
+                    let removed = removed.ok_or_else(|| tr!("job-connection-lost"))??;
+                    if removed.job == job {
+                        return job_result(&removed.unit, &removed.result);
+                    }
+                }
+                _ = check.tick() => {
+                    if backend.job_pending(&job).await {
+                        continue;
+                    }
+                    // The signal may still be waiting on the stream
+                    while let Some(Some(removed)) = removed_stream.next().now_or_never() {
+                        let removed = removed?;
+                        if removed.job == job {
+                            return job_result(&removed.unit, &removed.result);
+                        }
+                    }
+                    debug!("{} finished without a JobRemoved signal", job.as_str());
+                    return job_outcome(backend, unit, name, request).await;
+                }
+            }
+        }
+    };
+
+    time::timeout(timeout, removed)
+        .await
+        .unwrap_or_else(|_| Err(JobError::TimedOut(timeout).into()))
+}
+
+/// The result of a job that was never read, from the unit's `ActiveState`.
+/// Anything but the state the job was after fails, it is never taken as success.
+async fn job_outcome<B: SystemdBackend>(
+    backend: &B,
+    unit: &B::Unit,
+    name: &str,
+    request: JobRequest<'_>,
+) -> ExResult<()> {
+    let state = backend.active_state(unit).await?;
+    match (request, state.as_str()) {
+        (JobRequest::Start, "active" | "reloading") | (JobRequest::Stop(_), "inactive") => Ok(()),
+        (_, "failed") => job_result(name, "failed"),
+        (_, state) => Err(JobError::Unsettled {
+            unit: name.to_owned(),
+            state: state.to_owned(),
+        }
+        .into()),
+    }
+}
+
+const JOB_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the mount unit is checked while verifying an automount.
+const VERIFY_POLL: Duration = Duration::from_millis(250);
+
+fn job_result(unit: &str, result: &str) -> ExResult<()> {
+    match result {
+        "done" => Ok(()),
+        _ => Err(JobError::Failed {
+            unit: unit.to_owned(),
+            result: result.to_owned(),
+        }
+        .into()),
+    }
+}
+
+#[derive(Debug)]
+enum JobError {
+    Failed {
+        unit: String,
+        result: String,
+    },
+    /// The job's result went unseen and the unit isn't where it should be.
+    Unsettled {
+        unit: String,
+        state: String,
+    },
+    TimedOut(Duration),
+}
+
+impl std::fmt::Display for JobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Failed { unit, result } => {
+                write!(f, "{}", tr!("job-failed", unit = unit, result = result))
+            }
+            Self::Unsettled { unit, state } => {
+                write!(f, "{}", tr!("job-unsettled", unit = unit, state = state))
+            }
+            Self::TimedOut(timeout) => {
+                write!(f, "{}", tr!("job-timed-out", seconds = timeout.as_secs()))
+            }
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_check_the_polkit_actions_they_need() {
+        use ClientRequests::*;
+
+        let manage_units = [auth::MANAGE_UNITS];
+        for request in [
+            RequestDisconnect,
+            PrepareDisconnect,
+            EnableAutomounting,
+            DisableAutomounting,
+            Mount,
+            Unmount,
+            Remount,
+            EmergencyDisconnect,
+            ResetFailed,
+        ] {
+            assert_eq!(request.polkit_actions(false), manage_units, "{request:?}");
+        }
+        for request in [RequestDisconnect, PrepareDisconnect, DisconnectDrive] {
+            assert_eq!(
+                request.polkit_actions(true),
+                [auth::MANAGE_UNITS, auth::POWER_OFF_DRIVE],
+                "{request:?}"
+            );
+        }
+        assert_eq!(
+            DisconnectDrive.polkit_actions(false),
+            [auth::MANAGE_UNITS, auth::POWER_OFF_DRIVE]
+        );
+        assert_eq!(
+            SetAutomountAtBoot(true).polkit_actions(false),
+            [auth::MANAGE_UNIT_FILES, auth::RELOAD_DAEMON]
+        );
+        assert_eq!(ForceUnmount.polkit_actions(false), [auth::UNMOUNT_OTHERS]);
+        assert_eq!(
+            Unlock.polkit_actions(false),
+            [auth::UNLOCK_ENCRYPTED, auth::MANAGE_UNITS]
+        );
+        assert_eq!(ReloadDaemon.polkit_actions(false), [auth::RELOAD_DAEMON]);
+
+        // pkexec checks its own action, and the rest never reach systemd themselves
+        for request in [
+            SetReadOnly(true),
+            OpenFolder,
+            CopyPath,
+            SetQuiet(true),
+            Refresh,
+            MountAll,
+            DisconnectAll,
+            OpenAbout,
+            Quit,
+        ] {
+            assert!(request.polkit_actions(false).is_empty(), "{request:?}");
+        }
+    }
+
+    #[test]
+    fn failed_disconnects_offer_the_lazy_unmount() {
+        use ClientRequests::*;
+
+        for request in [
+            PrepareDisconnect,
+            DisconnectDrive,
+            EmergencyDisconnect,
+            Unmount,
+        ] {
+            assert!(
+                matches!(follow_up(&request, false), Some((_, _, ForceUnmount))),
+                "{request:?}"
+            );
+        }
+        assert!(matches!(
+            follow_up(&PrepareDisconnect, true),
+            Some((_, _, EnableAutomounting))
+        ));
+        assert!(follow_up(&DisconnectDrive, true).is_none());
+        assert!(follow_up(&Mount, false).is_none());
+    }
+
+    mod sub_states {
+        use super::*;
+        use backend::mock::MockSystemd;
+
+        const DEBOUNCE: Duration = Duration::from_millis(100);
+
+        fn systemd() -> MockSystemd {
+            let systemd = MockSystemd::default();
+            systemd.add_unit("mnt-backup.mount", "dead", "inactive");
+            systemd.add_unit("mnt-backup.automount", "waiting", "active");
+            systemd
+        }
+
+        /// Runs [`watch_sub_states`] on the mock, returning what it reports
+        /// once it has subscribed.
+        async fn watch(
+            systemd: &MockSystemd,
+            polling: Option<Duration>,
+        ) -> mpsc::UnboundedReceiver<(usize, StateChange)> {
+            let (changes, received) = mpsc::unbounded_channel();
+            let systemd = systemd.clone();
+            tokio::spawn(async move {
+                watch_sub_states(
+                    0,
+                    &systemd,
+                    ("mnt-backup.mount".to_owned(), "mnt-backup.mount"),
+                    Some(("mnt-backup.automount".to_owned(), "mnt-backup.automount")),
+                    DEBOUNCE,
+                    polling,
+                    &changes,
+                )
+                .await
+            });
+            time::sleep(Duration::from_millis(1)).await;
+            received
+        }
+
+        async fn next(received: &mut mpsc::UnboundedReceiver<(usize, StateChange)>) -> StateChange {
+            received.recv().await.expect("the watcher is running").1
+        }
+
+        async fn quiet(received: &mut mpsc::UnboundedReceiver<(usize, StateChange)>) -> bool {
+            time::timeout(Duration::from_secs(60), received.recv())
+                .await
+                .is_err()
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn bursts_settle_into_one_change() {
+            let systemd = systemd();
+            let mut received = watch(&systemd, None).await;
+
+            systemd.set_sub_state("mnt-backup.automount", "running");
+            systemd.set_sub_state("mnt-backup.mount", "mounting");
+            systemd.set_sub_state("mnt-backup.mount", "mounted");
+
+            assert!(
+                matches!(next(&mut received).await, StateChange::MountSubState(s) if s == "mounting")
+            );
+            assert!(
+                matches!(next(&mut received).await, StateChange::MountSubState(s) if s == "mounted")
+            );
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Mounted)
+            ));
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Automount(AutomountState::Running)
+            ));
+            assert!(quiet(&mut received).await);
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn ended_streams_are_resubscribed() {
+            let systemd = systemd();
+            let mut received = watch(&systemd, None).await;
+
+            // Missed while the stream was gone, read once subscribed again
+            systemd.end_sub_state_streams("mnt-backup.mount");
+            systemd.set_sub_state("mnt-backup.mount", "mounted");
+            assert!(
+                matches!(next(&mut received).await, StateChange::MountSubState(s) if s == "mounted")
+            );
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Mounted)
+            ));
+
+            systemd.set_sub_state("mnt-backup.mount", "unmounting");
+            assert!(
+                matches!(next(&mut received).await, StateChange::MountSubState(s) if s == "unmounting")
+            );
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Unmounting)
+            ));
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn polling_reports_changes_like_signals() {
+            let systemd = systemd();
+            systemd.set_signals(false);
+            let mut received = watch(&systemd, Some(Duration::from_secs(5))).await;
+            assert!(quiet(&mut received).await);
+
+            systemd.set_sub_state("mnt-backup.mount", "mounted");
+            assert!(
+                matches!(next(&mut received).await, StateChange::MountSubState(s) if s == "mounted")
+            );
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Mounted)
+            ));
+            assert!(quiet(&mut received).await);
+        }
+    }
+
+    mod jobs {
+        use super::*;
+        use backend::mock::{MockSystemd, Outcome};
+
+        const TIMEOUT: Duration = Duration::from_secs(30);
+
+        async fn start(outcome: Outcome) -> ExResult<()> {
+            let systemd = MockSystemd::default();
+            systemd.add_unit("mnt-backup.mount", "dead", "inactive");
+            systemd.set_outcome(outcome);
+            let unit = "mnt-backup.mount".to_owned();
+            job_wait(&systemd, &unit, &unit, JobRequest::Start, TIMEOUT).await
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn finished_jobs_succeed() {
+            start(Outcome::Removed("done")).await.unwrap();
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn failed_jobs_are_errors() {
+            let e = start(Outcome::Removed("failed")).await.unwrap_err();
+            assert!(matches!(
+                e.downcast_ref(),
+                Some(JobError::Failed { unit, result }) if unit == "mnt-backup.mount" && result == "failed"
+            ));
+        }
+
+        /// systemd can remove a job with nothing to do before its path is even
+        /// returned, the signal must still be the one the result is read from.
+        #[tokio::test(start_paused = true)]
+        async fn removals_ahead_of_the_job_path_are_read() {
+            start(Outcome::Removed("done")).await.unwrap();
+            let e = start(Outcome::Removed("dependency")).await.unwrap_err();
+            assert!(
+                matches!(e.downcast_ref(), Some(JobError::Failed { result, .. }) if result == "dependency")
+            );
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn unseen_results_come_from_the_unit() {
+            start(Outcome::Vanished("active")).await.unwrap();
+
+            let e = start(Outcome::Vanished("failed")).await.unwrap_err();
+            assert!(
+                matches!(e.downcast_ref(), Some(JobError::Failed { result, .. }) if result == "failed")
+            );
+
+            let e = start(Outcome::Vanished("inactive")).await.unwrap_err();
+            assert!(matches!(
+                e.downcast_ref(),
+                Some(JobError::Unsettled { state, .. }) if state == "inactive"
+            ));
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn queued_jobs_time_out() {
+            let e = start(Outcome::Pending).await.unwrap_err();
+            assert!(matches!(
+                e.downcast_ref(),
+                Some(JobError::TimedOut(TIMEOUT))
+            ));
+        }
+    }
+
+    mod state_loop {
+        use super::*;
+        use backend::mock::{MockSystemd, Outcome};
+
+        const DEBOUNCE: Duration = Duration::from_millis(100);
+        const TIMEOUT: Duration = Duration::from_secs(30);
+
+        type Received = mpsc::UnboundedReceiver<(usize, StateChange)>;
+
+        async fn units(systemd: &MockSystemd) -> Units<MockSystemd> {
+            systemd.add_unit("mnt-backup.mount", "dead", "inactive");
+            systemd.add_unit("mnt-backup.automount", "dead", "inactive");
+            Units::resolve(systemd, Instance::System, "mnt-backup", UnitKind::Mount)
+                .await
+                .unwrap()
+        }
+
+        /// Runs [`watch_states`] on the units, returning what it reports once
+        /// it has subscribed.
+        async fn watch(units: &Units<MockSystemd>) -> Received {
+            let (changes, received) = mpsc::unbounded_channel();
+            tokio::spawn(watch_states(0, units.clone(), DEBOUNCE, None, changes));
+            time::sleep(Duration::from_millis(1)).await;
+            received
+        }
+
+        /// The next change, past the raw sub-states ahead of it.
+        async fn next(received: &mut Received) -> StateChange {
+            loop {
+                match received.recv().await.expect("the watcher is running").1 {
+                    StateChange::MountSubState(_) => continue,
+                    change => return change,
+                }
+            }
+        }
+
+        async fn run(units: &Units<MockSystemd>, request: JobRequest<'_>) -> ExResult<()> {
+            job_wait(
+                &units.manager,
+                &units.mount,
+                &units.mount_name,
+                request,
+                TIMEOUT,
+            )
+            .await
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn units_without_a_file_are_missing() {
+            let systemd = MockSystemd::default();
+            systemd.add_unit("mnt-backup.mount", "dead", "inactive");
+            let missing = Units::missing(&systemd, "mnt-backup", UnitKind::Mount)
+                .await
+                .unwrap();
+            assert_eq!(missing, ["mnt-backup.automount"]);
+            systemd.add_unit("mnt-backup.automount", "dead", "inactive");
+            assert!(Units::missing(&systemd, "mnt-backup", UnitKind::Mount)
+                .await
+                .unwrap()
+                .is_empty());
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn mounts_unmounts_and_fails() {
+            let systemd = MockSystemd::default();
+            let units = units(&systemd).await;
+            let mut received = watch(&units).await;
+
+            run(&units, JobRequest::Start).await.unwrap();
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Mounted)
+            ));
+            assert_eq!(units.states().await.unwrap().0, MountState::Mounted);
+
+            run(&units, JobRequest::Stop("replace")).await.unwrap();
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Dead)
+            ));
+
+            systemd.set_outcome(Outcome::Removed("failed"));
+            assert!(run(&units, JobRequest::Start).await.is_err());
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Failed)
+            ));
+
+            systemd.reset_failed(&units.mount).await.unwrap();
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Dead)
+            ));
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn queued_jobs_show_until_removed() {
+            let systemd = MockSystemd::default();
+            let units = units(&systemd).await;
+            let mut received = watch(&units).await;
+
+            systemd.set_outcome(Outcome::Pending);
+            let queued = units.clone();
+            let job = tokio::spawn(async move { run(&queued, JobRequest::Start).await });
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Job(Some(PendingJob {
+                    kind: JobKind::Mounting,
+                    ..
+                }))
+            ));
+
+            systemd.finish_jobs("done");
+            assert!(matches!(next(&mut received).await, StateChange::Job(None)));
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::Mount(MountState::Mounted)
+            ));
+            job.await.unwrap().unwrap();
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn reloads_reread_the_unit_files() {
+            let systemd = MockSystemd::default();
+            let units = units(&systemd).await;
+            let mut received = watch(&units).await;
+            assert_eq!(units.automount_at_boot().await, Some(false));
+
+            systemd
+                .set_enabled("mnt-backup.automount", true)
+                .await
+                .unwrap();
+            systemd.reload().await.unwrap();
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::MountInfo(_)
+            ));
+            assert!(matches!(
+                next(&mut received).await,
+                StateChange::AutomountAtBoot(Some(true))
+            ));
         }
     }
 }
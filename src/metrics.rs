@@ -0,0 +1,189 @@
+//! Opt-in OpenMetrics endpoint, `GET /metrics` on `--metrics-addr`.
+//!
+//! ```text
+//! diski_mount_state{drive="mnt-backup",state="mounted"} 1
+//! diski_automount_state{drive="mnt-backup",state="waiting"} 1
+//! diski_mount_operations_total{drive="mnt-backup",operation="unmount",result="failure"} 2
+//! diski_free_bytes{drive="mnt-backup"} 1073741824
+//! ```
+//!
+//! The state gauges carry the raw systemd sub-state, free space is only
+//! reported for mounted drives and read when scraped.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tracing::{debug, warn};
+
+use crate::{control::Status, space::Space, state::MountState};
+
+/// Finished requests by drive, operation and whether they succeeded.
+#[derive(Default)]
+pub struct Operations(Mutex<BTreeMap<(String, &'static str, bool), u64>>);
+
+impl Operations {
+    pub fn record(&self, drive: &str, operation: &'static str, succeeded: bool) {
+        let mut counts = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        *counts
+            .entry((drive.to_owned(), operation, succeeded))
+            .or_default() += 1;
+    }
+}
+
+/// Binds the address and answers scrapes in the background.
+pub async fn serve(
+    addr: SocketAddr,
+    names: Vec<String>,
+    status: watch::Receiver<Vec<Status>>,
+    operations: Arc<Operations>,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    let names: Arc<[String]> = names.into();
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(respond(
+                        stream,
+                        names.clone(),
+                        status.clone(),
+                        operations.clone(),
+                    ));
+                }
+                Err(e) => {
+                    warn!("Metrics endpoint stopped accepting connections: {e}");
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+async fn respond(
+    stream: TcpStream,
+    names: Arc<[String]>,
+    status: watch::Receiver<Vec<Status>>,
+    operations: Arc<Operations>,
+) {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+
+    let Ok(Some(request)) = lines.next_line().await else {
+        return;
+    };
+    // The headers don't matter, but the client expects them to be read
+    while let Ok(Some(header)) = lines.next_line().await {
+        if header.is_empty() {
+            break;
+        }
+    }
+
+    let mut words = request.split_whitespace();
+    let response = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics" | "/")) => {
+            let statuses = status.borrow().clone();
+            let body = render(&names, &statuses, &operations).await;
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+        (Some("GET"), _) => empty("404 Not Found"),
+        _ => empty("405 Method Not Allowed"),
+    };
+
+    if let Err(e) = write.write_all(response.as_bytes()).await {
+        debug!("Could not answer a metrics scrape: {e}");
+    }
+}
+
+async fn render(names: &[String], statuses: &[Status], operations: &Operations) -> String {
+    let mut out = String::new();
+
+    out += "# TYPE diski_mount_state gauge\n";
+    out += "# HELP diski_mount_state Sub-state of the drive's mount unit.\n";
+    for (name, status) in names.iter().zip(statuses) {
+        let _ = writeln!(
+            out,
+            "diski_mount_state{{drive=\"{}\",state=\"{}\"}} 1",
+            label(name),
+            label(status.mount.as_str())
+        );
+    }
+
+    out += "# TYPE diski_automount_state gauge\n";
+    out += "# HELP diski_automount_state Sub-state of the drive's automount unit.\n";
+    for (name, status) in names.iter().zip(statuses) {
+        let _ = writeln!(
+            out,
+            "diski_automount_state{{drive=\"{}\",state=\"{}\"}} 1",
+            label(name),
+            label(status.automount.as_str())
+        );
+    }
+
+    out += "# TYPE diski_mount_operations counter\n";
+    out += "# HELP diski_mount_operations Requests handled for the drive.\n";
+    let counts = operations
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    for ((name, operation, succeeded), count) in counts {
+        let result = if succeeded { "success" } else { "failure" };
+        let _ = writeln!(
+            out,
+            "diski_mount_operations_total{{drive=\"{}\",operation=\"{operation}\",result=\"{result}\"}} {count}",
+            label(&name)
+        );
+    }
+
+    out += "# TYPE diski_free_bytes gauge\n";
+    out += "# UNIT diski_free_bytes bytes\n";
+    out += "# HELP diski_free_bytes Space available on the mounted drive.\n";
+    for (name, status) in names.iter().zip(statuses) {
+        let Some(path) = status
+            .path
+            .as_deref()
+            .filter(|_| status.mount == MountState::Mounted)
+        else {
+            continue;
+        };
+        if let Some(space) = Space::of(path).await {
+            let _ = writeln!(
+                out,
+                "diski_free_bytes{{drive=\"{}\"}} {}",
+                label(name),
+                space.free
+            );
+        }
+    }
+
+    out += "# EOF\n";
+    out
+}
+
+fn empty(status: &str) -> String {
+    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+}
+
+/// Escapes a label value as the exposition format requires.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
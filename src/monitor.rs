@@ -0,0 +1,107 @@
+use std::{
+    fmt,
+    io::{self, Write as _},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::sync::mpsc;
+
+use crate::{
+    config::{Instance, UnitKind, DEFAULT_POLL_INTERVAL},
+    exit_code::{self, Failure},
+    status::{self, json_string},
+    watch_states, Bus, StateChange, Units,
+};
+
+/// `diski watch`: prints a line per unit
+/// state change until killed, or a JSON object per line with `--json`.
+pub async fn run(args: status::Args) -> Result<(), Failure> {
+    let status::Args {
+        user,
+        bus_address,
+        json,
+        name,
+    } = args;
+    let instance = Instance::from_user(user);
+
+    let bus = Bus::connect(instance, bus_address, DEFAULT_POLL_INTERVAL)
+        .await
+        .map_err(|e| format!("could not connect to the {instance} bus: {e}"))?;
+    let (name, kind) = UnitKind::split(&name);
+    exit_code::found(Units::missing(&bus.manager, &name, kind).await?)?;
+    let units = Units::resolve(&bus.manager, bus.instance, &name, kind).await?;
+
+    let (changes, mut receiver) = mpsc::unbounded_channel();
+    // Subscribed before the current states are printed, so nothing falls in between
+    tokio::spawn(watch_states(
+        0,
+        units.clone(),
+        Duration::ZERO,
+        bus.polling,
+        changes,
+    ));
+
+    let (mount, automount) = units.states().await?;
+    print(json, "mount", mount.as_str())?;
+    print(json, "automount", automount.as_str())?;
+
+    while let Some((_, change)) = receiver.recv().await {
+        match change {
+            StateChange::Mount(state) => print(json, "mount", state.as_str())?,
+            StateChange::Automount(state) => print(json, "automount", state.as_str())?,
+            StateChange::BusLost => return Err(format!("lost the {instance} bus").into()),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn print(json: bool, unit: &str, state: &str) -> io::Result<()> {
+    let time = Timestamp(SystemTime::now());
+    let mut stdout = io::stdout().lock();
+    if json {
+        writeln!(
+            stdout,
+            r#"{{"time":"{time}","unit":{},"state":{}}}"#,
+            json_string(unit),
+            json_string(state)
+        )?;
+    } else {
+        writeln!(stdout, "{time} {unit} {state}")?;
+    }
+    stdout.flush()
+}
+
+/// RFC 3339 in UTC, without pulling in a date crate.
+pub struct Timestamp(pub SystemTime);
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (days, secs) = (secs / 86400, secs % 86400);
+
+        // Howard Hinnant's civil_from_days
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        write!(
+            f,
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+}
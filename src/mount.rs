@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use tokio::time;
+
+use crate::{
+    backend::SystemdBackend,
+    config::{self, Instance, UnitKind, DEFAULT_POLL_INTERVAL},
+    exit_code::{self, Failure},
+    job_wait,
+    state::MountState,
+    Bus, JobRequest, Units,
+};
+
+const SETTLE_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Use the user's systemd instance instead of the system one
+    #[arg(long, visible_alias = "session")]
+    pub user: bool,
+    /// Connect to this D-Bus address instead of the instance's
+    #[arg(long, value_name = "address")]
+    pub bus_address: Option<zbus::Address>,
+    /// Exit only once the drive is mounted
+    #[arg(long)]
+    pub wait: bool,
+    /// How long --wait waits, like "90s"
+    #[arg(long, value_name = "duration", default_value = "60s", value_parser = duration)]
+    pub timeout: Duration,
+    #[arg(value_name = "systemd name")]
+    pub name: String,
+}
+
+fn duration(value: &str) -> Result<Duration, String> {
+    config::parse_duration(value).ok_or_else(|| format!("`{value}` is not a duration like \"90s\""))
+}
+
+/// `diski mount`: starts the mount unit and, with `--wait`, exits only once
+/// it is mounted, non-zero when it failed or `--timeout` ran out first.
+pub async fn run(args: Args) -> Result<(), Failure> {
+    let Args {
+        user,
+        bus_address,
+        wait,
+        timeout,
+        name,
+    } = args;
+    let instance = Instance::from_user(user);
+
+    let bus = Bus::connect(instance, bus_address, DEFAULT_POLL_INTERVAL)
+        .await
+        .map_err(|e| format!("could not connect to the {instance} bus: {e}"))?;
+    let (name, kind) = UnitKind::split(&name);
+    exit_code::found(Units::missing(&bus.manager, &name, kind).await?)?;
+    let units = Units::resolve(&bus.manager, bus.instance, &name, kind).await?;
+    let unit = &units.mount_name;
+
+    if !wait {
+        bus.manager.start(&units.mount, "replace").await?;
+        return Ok(());
+    }
+
+    time::timeout(timeout, async {
+        job_wait(&bus.manager, &units.mount, unit, JobRequest::Start, timeout).await?;
+        // The job can finish before the sub-state catches up
+        loop {
+            match units.states().await?.0 {
+                MountState::Mounted => return Ok(()),
+                MountState::Failed => return Err(format!("{unit} failed").into()),
+                MountState::Dead => return Err(format!("{unit} stopped again").into()),
+                _ => time::sleep(SETTLE_POLL).await,
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| {
+        Err(Failure::TimedOut(format!(
+            "{unit} was not mounted within {}s",
+            timeout.as_secs()
+        )))
+    })
+}
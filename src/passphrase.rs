@@ -0,0 +1,66 @@
+use std::process::Stdio;
+
+use tokio::process::Command;
+use tracing::debug;
+
+/// Overwritten when dropped, so the passphrase doesn't linger on the heap
+/// after the container has been unlocked.
+pub struct Passphrase(Vec<u8>);
+
+impl Passphrase {
+    /// Trims the newline the helpers end their output with.
+    fn from_output(mut output: Vec<u8>) -> Option<Self> {
+        while output.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+            output.pop();
+        }
+        let passphrase = Self(output);
+        (!passphrase.0.is_empty()).then_some(passphrase)
+    }
+
+    pub fn as_str(&self) -> Result<&str, &'static str> {
+        std::str::from_utf8(&self.0).map_err(|_| "the passphrase is not valid UTF-8")
+    }
+}
+
+impl Drop for Passphrase {
+    fn drop(&mut self) {
+        for byte in self.0.iter_mut() {
+            // Volatile so the writes aren't optimized away as dead stores
+            unsafe { std::ptr::write_volatile(byte, 0) };
+        }
+    }
+}
+
+/// Looks the passphrase up in the Secret Service through libsecret's
+/// `secret-tool`, stored with `secret-tool store --label=… diski <systemd name>`,
+/// and otherwise asks for it with `systemd-ask-password`.
+pub async fn obtain(systemd_name: &str) -> Result<Passphrase, String> {
+    let stored = Command::new("secret-tool")
+        .args(["lookup", "diski", systemd_name])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await;
+    match stored {
+        Ok(output) if output.status.success() => {
+            if let Some(passphrase) = Passphrase::from_output(output.stdout) {
+                return Ok(passphrase);
+            }
+        }
+        Ok(_) => debug!("No passphrase for {systemd_name} in the Secret Service"),
+        Err(e) => debug!("Could not run secret-tool: {e}"),
+    }
+
+    let asked = Command::new("systemd-ask-password")
+        .arg(format!("--id=diski:{systemd_name}"))
+        .arg(format!("Passphrase to unlock {systemd_name}:"))
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("could not ask for a passphrase: {e}"))?;
+
+    if !asked.status.success() {
+        return Err("no passphrase was given".into());
+    }
+    Passphrase::from_output(asked.stdout).ok_or_else(|| "no passphrase was given".into())
+}
@@ -0,0 +1,125 @@
+//! Preferences changed from the tray, kept across restarts in
+//! `$XDG_STATE_HOME/diski/state.toml` rather than the config file:
+//!
+//! ```toml
+//! [drive."mnt-backup"]
+//! quiet = true
+//! ```
+//!
+//! `quiet` is the only preference the tray has to keep. Its other checkmarks,
+//! automounting, automount at boot and read-only, show what systemd and the
+//! mount table say and are read back from them. The tray host opens and
+//! closes submenus without telling diski, and the icons come from the config.
+//!
+//! Only the keys diski sets are touched when it is written back, anything else
+//! in the file, e.g. from a newer version, is kept as is.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use toml_edit::{table, value, DocumentMut};
+use tracing::warn;
+
+pub struct Preferences {
+    path: Option<PathBuf>,
+    doc: DocumentMut,
+}
+
+impl Preferences {
+    /// Starts over with no preferences when the file is missing or unreadable.
+    pub fn load() -> Self {
+        let path = state_home().map(|home| home.join("diski").join("state.toml"));
+        let doc = match path.as_deref().map(fs::read_to_string) {
+            Some(Ok(source)) => source.parse().unwrap_or_else(|e| {
+                warn!("Ignoring the saved preferences, they could not be read: {e}");
+                DocumentMut::new()
+            }),
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => {
+                warn!("Could not read the saved preferences: {e}");
+                DocumentMut::new()
+            }
+            _ => DocumentMut::new(),
+        };
+        Self { path, doc }
+    }
+
+    /// Whether successes for the drive go unannounced, `None` until set from the tray.
+    pub fn quiet(&self, systemd_name: &str) -> Option<bool> {
+        self.doc
+            .get("drive")?
+            .get(systemd_name)?
+            .get("quiet")?
+            .as_bool()
+    }
+
+    pub fn set_quiet(&mut self, systemd_name: &str, quiet: bool) {
+        let drives = self.doc.entry("drive").or_insert_with(table);
+        if !drives.is_table() {
+            *drives = table();
+        }
+        if let Some(drives) = drives.as_table_mut() {
+            drives.set_implicit(true);
+        }
+        let drive = &mut drives[systemd_name];
+        if !drive.is_table() {
+            *drive = table();
+        }
+        drive["quiet"] = value(quiet);
+        self.save();
+    }
+
+    /// Written next to the file and renamed over it, so a crash can't leave half of it.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            warn!("Not saving preferences, neither $XDG_STATE_HOME nor $HOME is set");
+            return;
+        };
+        if let Err(e) = write(path, &self.doc.to_string()) {
+            warn!("Could not save preferences to {}: {e}", path.display());
+        }
+    }
+}
+
+fn write(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("toml.tmp");
+    fs::write(&partial, contents)?;
+    fs::rename(&partial, path)
+}
+
+/// `$XDG_STATE_HOME`, or `~/.local/state` when it isn't set.
+fn state_home() -> Option<PathBuf> {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("state")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setting_quiet_keeps_the_rest_of_the_file() {
+        let source = "newer = 1\n\n[drive.\"mnt-backup\"]\nquiet = false\nexpanded = true\n";
+        let mut prefs = Preferences {
+            path: None,
+            doc: source.parse().unwrap(),
+        };
+        assert_eq!(prefs.quiet("mnt-backup"), Some(false));
+        assert_eq!(prefs.quiet("mnt-media"), None);
+
+        prefs.set_quiet("mnt-backup", true);
+        prefs.set_quiet("mnt-media", true);
+        assert_eq!(prefs.quiet("mnt-backup"), Some(true));
+        assert_eq!(prefs.quiet("mnt-media"), Some(true));
+
+        let written = prefs.doc.to_string();
+        assert!(written.contains("newer = 1"), "{written}");
+        assert!(written.contains("expanded = true"), "{written}");
+    }
+}
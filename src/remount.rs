@@ -0,0 +1,50 @@
+use std::process::Stdio;
+
+use tokio::{fs, process::Command};
+
+use crate::fstab;
+
+/// Whether the filesystem mounted at `path` is read-only, going by the
+/// topmost mount there in `/proc/self/mountinfo`.
+pub async fn read_only(path: &str) -> Option<bool> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").await.ok()?;
+
+    mountinfo.lines().rev().find_map(|line| {
+        let mut fields = line.split(' ').skip(4);
+        let (mount_point, options) = (fields.next()?, fields.next()?);
+        (fstab::unescape(mount_point) == path).then(|| options.split(',').any(|o| o == "ro"))
+    })
+}
+
+/// Remounts `path` in place through `pkexec`, which asks polkit itself.
+/// Neither systemd nor UDisks2 offer changing the options of a live mount.
+pub async fn remount(path: &str, read_only: bool) -> Result<(), String> {
+    let options = if read_only {
+        "remount,ro"
+    } else {
+        "remount,rw"
+    };
+    let output = Command::new("pkexec")
+        .args(["mount", "-o", options, "--", path])
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| format!("could not run pkexec: {e}"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    // mount says so when files are still open for writing
+    if stderr.contains("busy") {
+        return Err(format!("{path} is busy, files are still open for writing"));
+    }
+    // pkexec's own exit statuses for a dismissed or refused prompt
+    if matches!(output.status.code(), Some(126 | 127)) {
+        return Err("not authorized to remount".into());
+    }
+    Err(match stderr.trim() {
+        "" => format!("mount exited with {}", output.status),
+        message => message.to_owned(),
+    })
+}
@@ -0,0 +1,57 @@
+//! Sleep inhibitor locks from logind, taken when `inhibit_sleep` is set.
+//!
+//! A `block` lock is held while a request's jobs run, and a `delay` lock the
+//! rest of the time so mounted drives can be synced once logind announces a
+//! suspend. logind lives on the system bus for both instances.
+
+use futures::{Stream, StreamExt};
+use tracing::{debug, warn};
+use zbus::{proxy, zvariant::OwnedFd};
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Released when dropped, closing the last copy of the lock's descriptor.
+pub struct Inhibitor {
+    _fd: OwnedFd,
+}
+
+/// Keeps the system from sleeping until dropped with `block`, or holds the
+/// suspend back until dropped (or logind's delay runs out) with `delay`.
+/// Failing to take the lock is only logged, it never stops a request.
+pub async fn inhibit(conn: &zbus::Connection, mode: &str, why: &str) -> Option<Inhibitor> {
+    let taken = async {
+        ManagerProxy::new(conn)
+            .await?
+            .inhibit("sleep", "diski", why, mode)
+            .await
+    };
+    match taken.await {
+        Ok(fd) => {
+            debug!("Took a {mode} sleep inhibitor: {why}");
+            Some(Inhibitor { _fd: fd })
+        }
+        Err(e) => {
+            warn!("Could not take a {mode} sleep inhibitor: {e}");
+            None
+        }
+    }
+}
+
+/// `true` just before the system sleeps and `false` once it woke up again.
+pub async fn sleep_signals(conn: &zbus::Connection) -> zbus::Result<impl Stream<Item = bool>> {
+    let manager = ManagerProxy::new(conn).await?;
+    Ok(manager
+        .receive_prepare_for_sleep()
+        .await?
+        .filter_map(|signal| async move { signal.args().ok().map(|args| args.start) }))
+}
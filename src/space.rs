@@ -0,0 +1,62 @@
+use std::{fmt, time::Duration};
+
+use nix::sys::statvfs::statvfs;
+use tokio::{task, time};
+
+const STATVFS_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Space {
+    pub free: u64,
+    pub total: u64,
+}
+
+impl Space {
+    /// `None` when the path can't be queried, e.g. it disappeared mid-unmount
+    /// or the filesystem stopped responding.
+    // c_ulong and fsblkcnt_t are only u64 on 64-bit targets
+    #[allow(clippy::useless_conversion)]
+    pub async fn of(path: &str) -> Option<Self> {
+        let path = path.to_owned();
+        let stat = task::spawn_blocking(move || statvfs(path.as_str()));
+
+        let stat = time::timeout(STATVFS_TIMEOUT, stat)
+            .await
+            .ok()?
+            .ok()?
+            .ok()?;
+        let fragment = u64::from(stat.fragment_size());
+
+        Some(Self {
+            free: u64::from(stat.blocks_available()) * fragment,
+            total: u64::from(stat.blocks()) * fragment,
+        })
+    }
+}
+
+impl fmt::Display for Space {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} / {}", Bytes(self.free), Bytes(self.total))
+    }
+}
+
+pub struct Bytes(pub u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+        let mut value = self.0 as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 || value >= 100.0 {
+            write!(f, "{value:.0} {}", UNITS[unit])
+        } else {
+            write!(f, "{value:.1} {}", UNITS[unit])
+        }
+    }
+}
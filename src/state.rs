@@ -0,0 +1,169 @@
+use std::fmt;
+
+use crate::i18n::tr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MountState {
+    Mounted,
+    Mounting,
+    Unmounting,
+    Dead,
+    Failed,
+    Unknown(Box<str>),
+}
+
+impl MountState {
+    /// Also reads a swap unit's sub-states, `active` counting as mounted.
+    pub fn from_substates(input: &str) -> Self {
+        match input {
+            "mounted" | "mounting-done" | "active" | "activating-done" => Self::Mounted,
+            "mounting" | "activating" => Self::Mounting,
+            "unmounting" | "deactivating" => Self::Unmounting,
+            "dead" => Self::Dead,
+            "failed" => Self::Failed,
+            input => Self::Unknown(input.into()),
+        }
+    }
+
+    /// The systemd sub-state, for scripts rather than people.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Mounted => "mounted",
+            Self::Mounting => "mounting",
+            Self::Unmounting => "unmounting",
+            Self::Dead => "dead",
+            Self::Failed => "failed",
+            Self::Unknown(state) => state,
+        }
+    }
+
+    /// Whether going from `self` to `new` is worth acting on. Sub-states diski
+    /// doesn't know only count once, moving between them changes nothing shown.
+    pub fn changed_to(&self, new: &Self) -> bool {
+        !matches!((self, new), (Self::Unknown(_), Self::Unknown(_))) && self != new
+    }
+
+    /// Shown for a swap, which is active rather than mounted.
+    pub fn swap_label(&self) -> String {
+        match self {
+            Self::Mounted => tr!("swap-active"),
+            Self::Mounting => tr!("swap-activating"),
+            Self::Unmounting => tr!("swap-deactivating"),
+            Self::Dead => tr!("swap-dead"),
+            Self::Failed => tr!("mount-failed"),
+            Self::Unknown(state) => tr!("state-unknown", state = state),
+        }
+    }
+}
+
+impl fmt::Display for MountState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Mounted => tr!("mount-mounted"),
+            Self::Mounting => tr!("mount-mounting"),
+            Self::Unmounting => tr!("mount-unmounting"),
+            Self::Dead => tr!("mount-dead"),
+            Self::Failed => tr!("mount-failed"),
+            Self::Unknown(state) => tr!("state-unknown", state = state),
+        };
+        f.write_str(&label)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AutomountState {
+    Dead,
+    Waiting,
+    Running,
+    Failed,
+    Unknown(Box<str>),
+}
+
+impl AutomountState {
+    pub fn from_substates(input: &str) -> Self {
+        match input {
+            "dead" => Self::Dead,
+            "waiting" => Self::Waiting,
+            "running" => Self::Running,
+            "failed" => Self::Failed,
+            input => Self::Unknown(input.into()),
+        }
+    }
+
+    /// Like [`MountState::changed_to`].
+    pub fn changed_to(&self, new: &Self) -> bool {
+        !matches!((self, new), (Self::Unknown(_), Self::Unknown(_))) && self != new
+    }
+
+    /// The systemd sub-state, for scripts rather than people.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Dead => "dead",
+            Self::Waiting => "waiting",
+            Self::Running => "running",
+            Self::Failed => "failed",
+            Self::Unknown(state) => state,
+        }
+    }
+}
+
+impl fmt::Display for AutomountState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Dead => tr!("automount-dead"),
+            Self::Waiting => tr!("automount-waiting"),
+            Self::Running => tr!("automount-running"),
+            Self::Failed => tr!("automount-failed"),
+            Self::Unknown(state) => tr!("state-unknown", state = state),
+        };
+        f.write_str(&label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_substates_are_kept_raw() {
+        assert_eq!(
+            MountState::from_substates("cleaning"),
+            MountState::Unknown("cleaning".into())
+        );
+        assert_eq!(
+            AutomountState::from_substates("tentative"),
+            AutomountState::Unknown("tentative".into())
+        );
+        assert_eq!(MountState::from_substates("").as_str(), "");
+    }
+
+    #[test]
+    fn known_substates_are_recognised() {
+        assert_eq!(MountState::from_substates("mounted"), MountState::Mounted);
+        assert_eq!(
+            MountState::from_substates("activating"),
+            MountState::Mounting
+        );
+        assert_eq!(
+            AutomountState::from_substates("waiting"),
+            AutomountState::Waiting
+        );
+    }
+
+    #[test]
+    fn churn_between_unknown_substates_is_no_change() {
+        let a = MountState::from_substates("a");
+        let b = MountState::from_substates("b");
+        assert!(!a.changed_to(&b));
+        assert!(!a.changed_to(&a));
+        assert!(MountState::Mounted.changed_to(&a));
+        assert!(a.changed_to(&MountState::Dead));
+        assert!(MountState::Mounted.changed_to(&MountState::Dead));
+
+        let a = AutomountState::from_substates("a");
+        let b = AutomountState::from_substates("b");
+        assert!(!a.changed_to(&b));
+        assert!(AutomountState::Waiting.changed_to(&a));
+        assert!(a.changed_to(&AutomountState::Running));
+    }
+}
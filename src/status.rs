@@ -0,0 +1,108 @@
+use std::fmt::Write as _;
+
+use crate::{
+    config::{Instance, UnitKind, DEFAULT_POLL_INTERVAL},
+    exit_code::{self, Failure},
+    space::Space,
+    state::{AutomountState, MountState},
+    Bus, Units,
+};
+
+/// The options of `diski status`, and of `diski watch`.
+#[derive(Debug, clap::Args)]
+pub struct Args {
+    /// Use the user's systemd instance instead of the system one
+    #[arg(long, visible_alias = "session")]
+    pub user: bool,
+    /// Connect to this D-Bus address instead of the instance's
+    #[arg(long, value_name = "address")]
+    pub bus_address: Option<zbus::Address>,
+    /// Print JSON instead of key=value pairs
+    #[arg(long)]
+    pub json: bool,
+    #[arg(value_name = "systemd name")]
+    pub name: String,
+}
+
+/// `diski status`: prints the unit states once and exits.
+pub async fn run(args: Args) -> Result<(), Failure> {
+    let Args {
+        user,
+        bus_address,
+        json,
+        name,
+    } = args;
+    let instance = Instance::from_user(user);
+
+    let bus = Bus::connect(instance, bus_address, DEFAULT_POLL_INTERVAL)
+        .await
+        .map_err(|e| format!("could not connect to the {instance} bus: {e}"))?;
+    let (name, kind) = UnitKind::split(&name);
+    exit_code::found(Units::missing(&bus.manager, &name, kind).await?)?;
+    let units = Units::resolve(&bus.manager, bus.instance, &name, kind).await?;
+    let (mount, automount) = units.states().await?;
+
+    if !json {
+        println!("mount={} automount={}", mount.as_str(), automount.as_str());
+        return Ok(());
+    }
+
+    let path = units.mount_info().await.path;
+    let space = match &path {
+        Some(path) if mount == MountState::Mounted => Space::of(path).await,
+        _ => None,
+    };
+
+    let report = StatusReport {
+        mount,
+        automount,
+        path,
+        space,
+    };
+    println!("{}", report.to_json());
+
+    Ok(())
+}
+
+struct StatusReport {
+    mount: MountState,
+    automount: AutomountState,
+    path: Option<String>,
+    space: Option<Space>,
+}
+
+impl StatusReport {
+    /// Unknown sub-states are reported as systemd's raw string.
+    fn to_json(&self) -> String {
+        let number = |value: Option<u64>| value.map_or("null".into(), |v| v.to_string());
+
+        format!(
+            r#"{{"mount":{},"automount":{},"where":{},"free_bytes":{},"total_bytes":{}}}"#,
+            json_string(self.mount.as_str()),
+            json_string(self.automount.as_str()),
+            self.path.as_deref().map_or("null".into(), json_string),
+            number(self.space.map(|space| space.free)),
+            number(self.space.map(|space| space.total)),
+        )
+    }
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
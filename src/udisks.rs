@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+
+use tracing::debug;
+use zbus::{
+    proxy,
+    zvariant::{OwnedObjectPath, Value},
+};
+
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Manager",
+    default_service = "org.freedesktop.UDisks2",
+    default_path = "/org/freedesktop/UDisks2/Manager"
+)]
+trait Manager {
+    fn resolve_device(
+        &self,
+        devspec: HashMap<&str, Value<'_>>,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<Vec<zbus::zvariant::OwnedObjectPath>>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Block",
+    default_service = "org.freedesktop.UDisks2"
+)]
+trait Block {
+    #[zbus(property, name = "Device")]
+    fn device(&self) -> zbus::Result<Vec<u8>>;
+
+    #[zbus(property, name = "IdLabel")]
+    fn id_label(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "IdUUID")]
+    fn id_uuid(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "Drive")]
+    fn drive(&self) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property, name = "CryptoBackingDevice")]
+    fn crypto_backing_device(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Drive",
+    default_service = "org.freedesktop.UDisks2"
+)]
+trait Drive {
+    fn power_off(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+
+    #[zbus(property, name = "Removable")]
+    fn removable(&self) -> zbus::Result<bool>;
+
+    #[zbus(property, name = "CanPowerOff")]
+    fn can_power_off(&self) -> zbus::Result<bool>;
+
+    #[zbus(property, name = "Vendor")]
+    fn vendor(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "Model")]
+    fn model(&self) -> zbus::Result<String>;
+
+    #[zbus(property, name = "Serial")]
+    fn serial(&self) -> zbus::Result<String>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Drive.Ata",
+    default_service = "org.freedesktop.UDisks2"
+)]
+trait DriveAta {
+    #[zbus(property, name = "SmartSupported")]
+    fn smart_supported(&self) -> zbus::Result<bool>;
+
+    #[zbus(property, name = "SmartEnabled")]
+    fn smart_enabled(&self) -> zbus::Result<bool>;
+
+    #[zbus(property, name = "SmartFailing")]
+    fn smart_failing(&self) -> zbus::Result<bool>;
+
+    /// In kelvin, zero when the drive doesn't report it.
+    #[zbus(property, name = "SmartTemperature")]
+    fn smart_temperature(&self) -> zbus::Result<f64>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Filesystem",
+    default_service = "org.freedesktop.UDisks2"
+)]
+trait Filesystem {
+    fn unmount(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.UDisks2.Encrypted",
+    default_service = "org.freedesktop.UDisks2"
+)]
+trait Encrypted {
+    fn unlock(
+        &self,
+        passphrase: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(property, name = "CleartextDevice")]
+    fn cleartext_device(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// Finds the UDisks2 block object behind a mount's `What` source.
+async fn resolve(conn: &zbus::Connection, what: &str) -> zbus::Result<Option<OwnedObjectPath>> {
+    let devspec = match what.split_once('=') {
+        Some(("UUID", uuid)) => ("uuid", uuid),
+        Some(("LABEL", label)) => ("label", label),
+        _ if what.starts_with("/dev/") => ("path", what),
+        _ => return Ok(None),
+    };
+
+    let manager = ManagerProxy::new(conn).await?;
+    let devices = manager
+        .resolve_device(
+            HashMap::from([(devspec.0, Value::from(devspec.1))]),
+            HashMap::new(),
+        )
+        .await?;
+
+    Ok(devices.into_iter().next())
+}
+
+/// Detaches the filesystem even while files on it are open, the kernel
+/// finishes the unmount once the last of them is closed. UDisks2 does its
+/// own polkit check for unmounting something another user mounted.
+pub async fn unmount_lazily(conn: &zbus::Connection, what: &str) -> zbus::Result<()> {
+    let Some(path) = resolve(conn, what).await? else {
+        return Err(zbus::Error::Failure(format!(
+            "UDisks2 does not know {what}"
+        )));
+    };
+
+    FilesystemProxy::builder(conn)
+        .path(path)?
+        .build()
+        .await?
+        .unmount(HashMap::from([("force", Value::from(true))]))
+        .await
+}
+
+/// The drive object behind the source, unlocked LUKS containers count as
+/// their backing device's.
+async fn physical_drive(conn: &zbus::Connection, what: &str) -> Option<OwnedObjectPath> {
+    let path = resolve(conn, what).await.ok()??;
+    drive_behind(conn, path).await
+}
+
+/// The drive object behind the block device, following LUKS containers to
+/// their backing device. `None` for loop devices, LVM and the like.
+async fn drive_behind(
+    conn: &zbus::Connection,
+    mut path: OwnedObjectPath,
+) -> Option<OwnedObjectPath> {
+    loop {
+        let block = BlockProxy::builder(conn)
+            .path(path)
+            .ok()?
+            .build()
+            .await
+            .ok()?;
+        let drive = block.drive().await.ok()?;
+        if drive.as_str() != "/" {
+            return Some(drive);
+        }
+        path = block.crypto_backing_device().await.ok()?;
+        if path.as_str() == "/" {
+            return None;
+        }
+    }
+}
+
+/// The physical drive holding the mount's source, with its vendor and model
+/// as a name for it.
+pub async fn drive_of(conn: &zbus::Connection, what: &str) -> Option<(OwnedObjectPath, String)> {
+    let drive = physical_drive(conn, what).await?;
+
+    let proxy = DriveProxy::builder(conn)
+        .path(drive.clone())
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    let (vendor, model) = (
+        proxy.vendor().await.unwrap_or_default(),
+        proxy.model().await.unwrap_or_default(),
+    );
+    let name = format!("{} {}", vendor.trim(), model.trim());
+    debug!("{what} is on {}", drive.as_str());
+    Some((drive, name.trim().to_owned()))
+}
+
+/// The serial number of the physical drive holding the mount's source, if it reports one.
+pub async fn serial_of(conn: &zbus::Connection, what: &str) -> Option<String> {
+    let drive = physical_drive(conn, what).await?;
+    let proxy = DriveProxy::builder(conn)
+        .path(drive)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    non_empty(proxy.serial().await.ok()?)
+}
+
+/// What SMART says about the drive holding the mount's source.
+#[derive(Debug, Clone, Copy)]
+pub struct Health {
+    pub failing: bool,
+    /// In degrees Celsius.
+    pub temperature: Option<f64>,
+}
+
+/// `None` when the drive isn't ATA or has SMART turned off, as USB bridges
+/// that don't pass it through often do.
+pub async fn health(conn: &zbus::Connection, what: &str) -> Option<Health> {
+    let drive = physical_drive(conn, what).await?;
+    let ata = DriveAtaProxy::builder(conn)
+        .path(drive)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+    if !ata.smart_supported().await.ok()? || !ata.smart_enabled().await.ok()? {
+        return None;
+    }
+
+    let kelvin = ata.smart_temperature().await.unwrap_or_default();
+    Some(Health {
+        failing: ata.smart_failing().await.ok()?,
+        temperature: (kelvin > 0.0).then_some(kelvin - 273.15),
+    })
+}
+
+/// Whether the LUKS container is still locked, `None` when it isn't one or
+/// UDisks2 can't tell.
+pub async fn is_locked(conn: &zbus::Connection, device: &str) -> Option<bool> {
+    let path = resolve(conn, device).await.ok()??;
+    let encrypted = EncryptedProxy::builder(conn)
+        .path(path)
+        .ok()?
+        .build()
+        .await
+        .ok()?;
+
+    let cleartext = encrypted.cleartext_device().await.ok()?;
+    Some(cleartext.as_str() == "/")
+}
+
+pub async fn unlock(conn: &zbus::Connection, device: &str, passphrase: &str) -> zbus::Result<()> {
+    let Some(path) = resolve(conn, device).await? else {
+        return Err(zbus::Error::Failure(format!(
+            "UDisks2 does not know {device}"
+        )));
+    };
+
+    EncryptedProxy::builder(conn)
+        .path(path)?
+        .build()
+        .await?
+        .unlock(passphrase, HashMap::new())
+        .await?;
+    Ok(())
+}
+
+/// Spins down and cuts power to the drive holding the mount's source, so it
+/// can be unplugged, behind the LUKS container for an encrypted one. False
+/// when it isn't a removable drive that supports it.
+pub async fn power_off(conn: &zbus::Connection, what: &str) -> zbus::Result<bool> {
+    let Some(path) = resolve(conn, what).await? else {
+        return Err(zbus::Error::Failure(format!(
+            "UDisks2 does not know {what}"
+        )));
+    };
+
+    // Loop devices, LVM and the like have no drive behind them
+    let Some(path) = drive_behind(conn, path).await else {
+        return Ok(false);
+    };
+
+    let drive = DriveProxy::builder(conn).path(path)?.build().await?;
+    if !drive.removable().await? || !drive.can_power_off().await? {
+        return Ok(false);
+    }
+
+    drive.power_off(HashMap::new()).await?;
+    Ok(true)
+}
+
+/// What physically backs a mount, so identical looking drives can be told apart.
+#[derive(Debug, Clone, Default)]
+pub struct Identity {
+    pub label: Option<String>,
+    pub uuid: Option<String>,
+    pub device: Option<String>,
+}
+
+impl Identity {
+    /// Asks UDisks2 about the mount's `What` source, falling back to whatever
+    /// the source itself spells out when UDisks2 isn't running or doesn't know it.
+    pub async fn lookup(conn: &zbus::Connection, what: &str) -> Self {
+        match Self::from_udisks(conn, what).await {
+            Ok(Some(identity)) => identity,
+            Ok(None) => Self::from_source(what).await,
+            Err(e) => {
+                debug!("UDisks2 lookup of {what} failed: {e}");
+                Self::from_source(what).await
+            }
+        }
+    }
+
+    async fn from_udisks(conn: &zbus::Connection, what: &str) -> zbus::Result<Option<Self>> {
+        let Some(path) = resolve(conn, what).await? else {
+            return Ok(None);
+        };
+        let block = BlockProxy::builder(conn).path(path)?.build().await?;
+
+        let mut device = block.device().await?;
+        if device.last() == Some(&0) {
+            device.pop();
+        }
+
+        Ok(Some(Self {
+            label: non_empty(block.id_label().await?),
+            uuid: non_empty(block.id_uuid().await?),
+            device: non_empty(String::from_utf8_lossy(&device).into_owned()),
+        }))
+    }
+
+    async fn from_source(what: &str) -> Self {
+        let mut identity = Self::default();
+
+        if let Some(uuid) = what
+            .strip_prefix("UUID=")
+            .or_else(|| what.strip_prefix("/dev/disk/by-uuid/"))
+        {
+            identity.uuid = Some(unescape(uuid));
+        } else if let Some(label) = what
+            .strip_prefix("LABEL=")
+            .or_else(|| what.strip_prefix("/dev/disk/by-label/"))
+        {
+            identity.label = Some(unescape(label));
+        }
+
+        if what.starts_with("/dev/") {
+            // Symlinks like /dev/disk/by-uuid only resolve while the device is plugged in
+            identity.device = tokio::fs::canonicalize(what)
+                .await
+                .ok()
+                .map(|path| path.display().to_string());
+        }
+
+        identity
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    Some(value).filter(|v| !v.is_empty())
+}
+
+/// Undoes udev's `\x20` style escaping in `/dev/disk/by-*` names.
+fn unescape(name: &str) -> String {
+    let mut out = Vec::with_capacity(name.len());
+    let mut bytes = name.as_bytes();
+
+    while let Some((&byte, rest)) = bytes.split_first() {
+        let escaped = rest
+            .strip_prefix(b"x")
+            .filter(|_| byte == b'\\')
+            .and_then(|hex| hex.get(..2))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+
+        match escaped {
+            Some(value) => {
+                out.push(value);
+                bytes = &rest[3..];
+            }
+            None => {
+                out.push(byte);
+                bytes = rest;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
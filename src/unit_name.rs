@@ -0,0 +1,134 @@
+use std::{fmt::Write as _, fs, io};
+
+use crate::fstab;
+
+/// The unit name prefix systemd gives the mount at `path`, matching
+/// `systemd-escape --path` so `/mnt/my-backup` becomes `mnt-my\x2dbackup`.
+pub fn from_mount_point(path: &str) -> String {
+    let components: Vec<&str> = path
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    if components.is_empty() {
+        return "-".into();
+    }
+
+    let mut name = String::with_capacity(path.len());
+    for (i, component) in components.iter().enumerate() {
+        if i > 0 {
+            name.push('-');
+        }
+        for (j, &byte) in component.as_bytes().iter().enumerate() {
+            let plain = byte.is_ascii_alphanumeric()
+                || byte == b':'
+                || byte == b'_'
+                || (byte == b'.' && (i, j) != (0, 0));
+            if plain {
+                name.push(byte as char);
+            } else {
+                let _ = write!(name, "\\x{byte:02x}");
+            }
+        }
+    }
+
+    name
+}
+
+/// Looks the device up in fstab and names the unit of its mount point.
+/// `UUID=` and `LABEL=` style sources match their `/dev/disk/by-*` links.
+pub fn from_device(device: &str) -> io::Result<Option<String>> {
+    let wanted = fs::canonicalize(device).ok();
+
+    for entry in fstab::read()? {
+        let source = device_path(&entry.source);
+        let same = source == device || wanted.is_some() && fs::canonicalize(&source).ok() == wanted;
+        if same {
+            return Ok(Some(from_mount_point(&entry.mount_point)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The `/dev` path an fstab source refers to.
+pub fn device_path(source: &str) -> String {
+    match source.split_once('=') {
+        Some(("UUID", uuid)) => format!("/dev/disk/by-uuid/{uuid}"),
+        Some(("LABEL", label)) => format!("/dev/disk/by-label/{}", escape_udev(label)),
+        Some(("PARTUUID", uuid)) => format!("/dev/disk/by-partuuid/{uuid}"),
+        Some(("PARTLABEL", label)) => format!("/dev/disk/by-partlabel/{}", escape_udev(label)),
+        _ => source.to_owned(),
+    }
+}
+
+/// The unit name with everything but ASCII letters and digits turned into `_`,
+/// safe in D-Bus object paths and names. Tray ids and the drives' object paths
+/// are both made with it, so they name a drive the same way.
+pub fn identifier(systemd_name: &str) -> String {
+    systemd_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The reverse of the unescaping in `udisks`, as udev names `/dev/disk/by-label` links.
+fn escape_udev(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if !c.is_ascii() || c.is_ascii_alphanumeric() || "#+-.:=@_".contains(c) {
+            out.push(c);
+        } else {
+            let _ = write!(out, "\\x{:02x}", c as u32);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Expected names are what `systemd-escape --path` prints.
+    #[test]
+    fn mount_points_match_systemd_escape() {
+        for (path, unit) in [
+            ("/", "-"),
+            ("/mnt/backup", "mnt-backup"),
+            ("/mnt/my-backup", "mnt-my\\x2dbackup"),
+            ("/.hidden", "\\x2ehidden"),
+            ("/mnt/a.b", "mnt-a.b"),
+            ("/mnt/backup/", "mnt-backup"),
+            ("//mnt//backup", "mnt-backup"),
+            ("/mnt/my disk", "mnt-my\\x20disk"),
+            ("/mnt/ä", "mnt-\\xc3\\xa4"),
+        ] {
+            assert_eq!(
+                format!("{}.mount", from_mount_point(path)),
+                format!("{unit}.mount")
+            );
+        }
+    }
+
+    #[test]
+    fn identifiers_are_safe_in_object_paths() {
+        assert_eq!(identifier("mnt-backup"), "mnt_backup");
+        assert_eq!(identifier("mnt-my\\x2dbackup"), "mnt_my_x2dbackup");
+        assert_eq!(identifier("dev-sdb2"), "dev_sdb2");
+        assert!(zbus::zvariant::ObjectPath::try_from(format!(
+            "/org/diski/Drive/{}",
+            identifier("mnt-\\xc3\\xa4 .")
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn fstab_sources_name_their_dev_links() {
+        assert_eq!(device_path("UUID=1234-ABCD"), "/dev/disk/by-uuid/1234-ABCD");
+        assert_eq!(
+            device_path("LABEL=My Disk"),
+            "/dev/disk/by-label/My\\x20Disk"
+        );
+        assert_eq!(device_path("LABEL=a/b"), "/dev/disk/by-label/a\\x2fb");
+        assert_eq!(device_path("/dev/sdb1"), "/dev/sdb1");
+    }
+}